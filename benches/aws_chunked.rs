@@ -0,0 +1,76 @@
+//aws-chunked encoding throughput, run with `cargo bench --bench aws_chunked`
+//input size in GiB is read from BENCH_GB, default 2
+use example_signer::example_signer;
+//example_signer.rs names the signer by its path in the library
+use sloppy_auth::aws::{self, checksum::ChecksumAlgorithm, s3};
+use std::io::{self, Read};
+use std::time::Instant;
+
+#[path = "../src/aws/example_signer.rs"]
+mod example_signer;
+
+const CHUNK_SIZE: usize = 1024 * 1024;
+const READ_BUF: usize = 256 * 1024;
+
+fn drain<R: Read>(mut reader: R) -> u64 {
+    let mut buf = vec![0u8; READ_BUF];
+    let mut total = 0;
+    loop {
+        let len = reader.read(&mut buf).expect("read failed");
+        std::hint::black_box(&buf);
+        if len == 0 {
+            return total;
+        }
+        total += len as u64;
+    }
+}
+
+fn run<R: Read>(name: &str, decoded_len: u64, reader: R) {
+    let start = Instant::now();
+    let encoded_len = drain(reader);
+    let secs = start.elapsed().as_secs_f64();
+    println!(
+        "{:<28} {:>12} bytes {:>8.2}s {:>10.1} MiB/s",
+        name,
+        encoded_len,
+        secs,
+        decoded_len as f64 / secs / 1024.0 / 1024.0
+    );
+}
+
+fn main() {
+    let gb = std::env::var("BENCH_GB")
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .unwrap_or(2);
+    let len = gb * 1024 * 1024 * 1024;
+    let source = || io::repeat(97).take(len);
+
+    run("raw copy", len, source());
+    run(
+        "signed",
+        len,
+        s3::Holder::new(CHUNK_SIZE, source(), example_signer(s3::STREAM_PAYLOAD)),
+    );
+    run(
+        "signed + crc32c trailer",
+        len,
+        s3::Holder::new(
+            CHUNK_SIZE,
+            source(),
+            example_signer(s3::STREAM_PAYLOAD_TRAILER),
+        )
+        .with_checksum(ChecksumAlgorithm::Crc32c),
+    );
+    run(
+        "unsigned + crc32c trailer",
+        len,
+        s3::Holder::new(
+            CHUNK_SIZE,
+            source(),
+            example_signer(s3::STREAM_UNSIGNED_PAYLOAD_TRAILER),
+        )
+        .unsigned()
+        .with_checksum(ChecksumAlgorithm::Crc32c),
+    );
+}
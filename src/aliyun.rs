@@ -67,9 +67,10 @@ pub mod oss {
         pub canonicalized_resource: String,
     }
 
-    impl ToString for StringToSign {
-        fn to_string(&self) -> String {
-            format!(
+    impl std::fmt::Display for StringToSign {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(
+                f,
                 "{}\n{}\n{}\n{}\n{}{}",
                 self.verb,
                 self.content_md5,
@@ -0,0 +1,148 @@
+// https://docs.aws.amazon.com/AmazonS3/latest/userguide/checking-object-integrity.html
+use crc::{Crc, Digest, Table, CRC_32_ISCSI, CRC_32_ISO_HDLC};
+use ring::digest;
+
+//slice-by-16 tables, checksums run over every uploaded byte
+type CrcTable = Table<16>;
+static CRC32: Crc<u32, CrcTable> = Crc::<u32, CrcTable>::new(&CRC_32_ISO_HDLC);
+static CRC32C: Crc<u32, CrcTable> = Crc::<u32, CrcTable>::new(&CRC_32_ISCSI);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChecksumAlgorithm {
+    Crc32,
+    Crc32c,
+    Sha1,
+    Sha256,
+}
+
+impl ChecksumAlgorithm {
+    //header carrying the checksum, also the value of x-amz-trailer
+    pub fn header_name(&self) -> &'static str {
+        match self {
+            Self::Crc32 => "x-amz-checksum-crc32",
+            Self::Crc32c => "x-amz-checksum-crc32c",
+            Self::Sha1 => "x-amz-checksum-sha1",
+            Self::Sha256 => "x-amz-checksum-sha256",
+        }
+    }
+
+    //value of x-amz-sdk-checksum-algorithm
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Crc32 => "CRC32",
+            Self::Crc32c => "CRC32C",
+            Self::Sha1 => "SHA1",
+            Self::Sha256 => "SHA256",
+        }
+    }
+
+    //length of the raw digest in bytes
+    pub fn digest_len(&self) -> usize {
+        match self {
+            Self::Crc32 | Self::Crc32c => 4,
+            Self::Sha1 => 20,
+            Self::Sha256 => 32,
+        }
+    }
+
+    //length of the base64 encoded digest
+    pub fn encoded_len(&self) -> usize {
+        self.digest_len().div_ceil(3) * 4
+    }
+
+    pub fn checksum(&self) -> Checksum {
+        Checksum::new(*self)
+    }
+}
+
+//incremental checksum state
+pub enum Checksum {
+    Crc32(Digest<'static, u32, CrcTable>),
+    Crc32c(Digest<'static, u32, CrcTable>),
+    Sha1(digest::Context),
+    Sha256(digest::Context),
+}
+
+impl Checksum {
+    pub fn new(algorithm: ChecksumAlgorithm) -> Self {
+        match algorithm {
+            ChecksumAlgorithm::Crc32 => Self::Crc32(CRC32.digest()),
+            ChecksumAlgorithm::Crc32c => Self::Crc32c(CRC32C.digest()),
+            ChecksumAlgorithm::Sha1 => {
+                Self::Sha1(digest::Context::new(&digest::SHA1_FOR_LEGACY_USE_ONLY))
+            }
+            ChecksumAlgorithm::Sha256 => Self::Sha256(digest::Context::new(&digest::SHA256)),
+        }
+    }
+
+    pub fn algorithm(&self) -> ChecksumAlgorithm {
+        match self {
+            Self::Crc32(_) => ChecksumAlgorithm::Crc32,
+            Self::Crc32c(_) => ChecksumAlgorithm::Crc32c,
+            Self::Sha1(_) => ChecksumAlgorithm::Sha1,
+            Self::Sha256(_) => ChecksumAlgorithm::Sha256,
+        }
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        match self {
+            Self::Crc32(d) | Self::Crc32c(d) => d.update(data),
+            Self::Sha1(c) | Self::Sha256(c) => c.update(data),
+        }
+    }
+
+    pub fn finalize(self) -> Vec<u8> {
+        match self {
+            Self::Crc32(d) | Self::Crc32c(d) => d.finalize().to_be_bytes().to_vec(),
+            Self::Sha1(c) | Self::Sha256(c) => c.finish().as_ref().to_vec(),
+        }
+    }
+
+    //checksum value as sent in headers and trailers
+    pub fn finalize_base64(self) -> String {
+        base64::encode(self.finalize())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checksum_values() {
+        let check = b"123456789";
+        let expected = vec![
+            (ChecksumAlgorithm::Crc32, "cbf43926"),
+            (ChecksumAlgorithm::Crc32c, "e3069283"),
+            (
+                ChecksumAlgorithm::Sha1,
+                "f7c3bc1d808e04732adf679965ccc34ca7ae3441",
+            ),
+            (
+                ChecksumAlgorithm::Sha256,
+                "15e2b0d3c33891ebb0f1ef609ec419420c20e320ce94c65fbc8c3312448eb225",
+            ),
+        ];
+        for (algorithm, hex_digest) in expected {
+            let mut checksum = algorithm.checksum();
+            checksum.update(&check[0..4]);
+            checksum.update(&check[4..]);
+            let value = checksum.finalize();
+            assert_eq!(hex::encode(&value), hex_digest);
+            assert_eq!(value.len(), algorithm.digest_len());
+            assert_eq!(
+                base64::encode(&value).len(),
+                algorithm.encoded_len(),
+                "{:?}",
+                algorithm
+            );
+        }
+    }
+
+    #[test]
+    fn checksum_base64() {
+        let mut checksum = ChecksumAlgorithm::Crc32c.checksum();
+        checksum.update(b"");
+        assert_eq!(checksum.finalize_base64(), "AAAAAA==");
+    }
+}
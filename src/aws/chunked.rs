@@ -0,0 +1,409 @@
+// https://docs.aws.amazon.com/AmazonS3/latest/API/sigv4-streaming.html
+use crate::{
+    aws::{
+        auth::Sign,
+        checksum::{Checksum, ChecksumAlgorithm},
+        s3::S3Chunk,
+    },
+    util::Headers,
+};
+use ring::hmac;
+use std::io::{BufRead, BufReader, Error, ErrorKind, Read, Result};
+
+//signs and frames aws-chunked chunks, shared by the blocking and async uploaders
+pub struct Encoder<H: Headers> {
+    signer: Sign<H>,
+    signing_key: hmac::Key,
+    prev_signature: Option<String>,
+    signed: bool,
+    checksum: Option<Checksum>,
+}
+
+impl<H: Headers> Encoder<H> {
+    pub fn new(signer: Sign<H>) -> Self {
+        let signing_key = hmac::Key::new(hmac::HMAC_SHA256, &signer.signing_key());
+        Self {
+            signer,
+            signing_key,
+            prev_signature: None,
+            signed: true,
+            checksum: None,
+        }
+    }
+
+    //compute a checksum over all chunks and send it in the trailer
+    pub fn with_checksum(mut self, algorithm: ChecksumAlgorithm) -> Self {
+        self.checksum = Some(algorithm.checksum());
+        self
+    }
+
+    //frames without chunk signatures
+    pub fn unsigned(mut self) -> Self {
+        self.signed = false;
+        self
+    }
+
+    pub fn signer(&self) -> &Sign<H> {
+        &self.signer
+    }
+
+    fn sign(&self, string_to_sign: String) -> String {
+        hex::encode(hmac::sign(&self.signing_key, string_to_sign.as_bytes()))
+    }
+
+    fn prev_signature(&mut self) -> String {
+        match self.prev_signature.take() {
+            Some(s) => s,
+            None => self.signer.calc_seed_signature(),
+        }
+    }
+
+    //header line of a chunk, followed by the data and CRLF
+    pub fn chunk_header(&mut self, data: &[u8]) -> String {
+        if let Some(checksum) = self.checksum.as_mut() {
+            checksum.update(data);
+        }
+        if !self.signed {
+            return format!("{:x}\r\n", data.len());
+        }
+
+        let prev = self.prev_signature();
+        let signature = self.sign(self.signer.chunk_string_to_sign(prev, data));
+        let header = format!("{:x};chunk-signature={}\r\n", data.len(), signature);
+        self.prev_signature = Some(signature);
+        header
+    }
+
+    //zero-length final chunk with the optional trailer, ends the body
+    pub fn final_chunk(&mut self) -> String {
+        let trailer = self.checksum.take().map(|checksum| {
+            format!(
+                "{}:{}",
+                checksum.algorithm().header_name(),
+                checksum.finalize_base64()
+            )
+        });
+        if !self.signed {
+            return match trailer {
+                Some(trailer) => format!("0\r\n{}\r\n\r\n", trailer),
+                None => "0\r\n\r\n".to_string(),
+            };
+        }
+
+        let prev = self.prev_signature();
+        log::debug!("Encoder final chunk prev signature {}", prev);
+        let signature = self.sign(self.signer.chunk_string_to_sign(prev, &[]));
+        self.prev_signature = Some(signature.clone());
+        match trailer {
+            Some(trailer) => {
+                let trailer_sign = self.sign(
+                    self.signer
+                        .trailer_string_to_sign(signature.clone(), trailer.clone()),
+                );
+                self.prev_signature = Some(trailer_sign.clone());
+                format!(
+                    "0;chunk-signature={}\r\n{}\r\nx-amz-trailer-signature:{}\r\n\r\n",
+                    signature, trailer, trailer_sign
+                )
+            }
+            None => format!("0;chunk-signature={}\r\n\r\n", signature),
+        }
+    }
+}
+
+//upper bound for a single chunk, protects against hostile size lines
+pub const MAX_CHUNK_SIZE: usize = 64 * 1024 * 1024;
+const MAX_LINE_LEN: u64 = 4096;
+
+//decodes an aws-chunked body, verifying every chunk signature and the trailer
+//before the chunk data is handed out
+pub struct Decoder<R: Read, H: Headers> {
+    reader: BufReader<R>,
+    signer: Sign<H>,
+    prev_signature: Option<String>,
+    signed: bool,
+    checksum: Option<Checksum>,
+    expected_len: Option<u64>,
+    decoded_len: u64,
+    data: Vec<u8>,
+    pos: usize,
+    finished: bool,
+}
+
+impl<R: Read, H: Headers> Decoder<R, H> {
+    //signer must be built from the request headers, its seed signature
+    //is the first previous signature
+    pub fn new(reader: R, signer: Sign<H>) -> Self {
+        Self {
+            reader: BufReader::new(reader),
+            signer,
+            prev_signature: None,
+            signed: true,
+            checksum: None,
+            expected_len: None,
+            decoded_len: 0,
+            data: Vec::new(),
+            pos: 0,
+            finished: false,
+        }
+    }
+
+    //expect a checksum trailer, the value from x-amz-trailer
+    pub fn with_checksum(mut self, algorithm: ChecksumAlgorithm) -> Self {
+        self.checksum = Some(algorithm.checksum());
+        self
+    }
+
+    //expect chunks without signatures (STREAMING-UNSIGNED-PAYLOAD-TRAILER)
+    pub fn unsigned(mut self) -> Self {
+        self.signed = false;
+        self
+    }
+
+    //check the total against x-amz-decoded-content-length
+    pub fn with_decoded_length(mut self, len: u64) -> Self {
+        self.expected_len = Some(len);
+        self
+    }
+
+    fn read_line(&mut self) -> Result<String> {
+        let mut line = Vec::new();
+        (&mut self.reader)
+            .take(MAX_LINE_LEN)
+            .read_until(b'\n', &mut line)?;
+        if !line.ends_with(b"\r\n") {
+            return Err(invalid("chunk line not terminated by CRLF"));
+        }
+        line.truncate(line.len() - 2);
+        String::from_utf8(line).map_err(|_| invalid("chunk line is not utf8"))
+    }
+
+    fn prev_signature(&mut self) -> String {
+        match self.prev_signature.take() {
+            Some(s) => s,
+            None => self.signer.calc_seed_signature(),
+        }
+    }
+
+    fn next_chunk(&mut self) -> Result<()> {
+        let line = self.read_line()?;
+        let (size, signature) = match line.split_once(';') {
+            Some((size, ext)) => match ext.strip_prefix("chunk-signature=") {
+                Some(sig) if self.signed => (size, Some(sig.to_string())),
+                _ => return Err(invalid("unexpected chunk extension")),
+            },
+            None if !self.signed => (line.as_str(), None),
+            None => return Err(invalid("missing chunk signature")),
+        };
+        let size = usize::from_str_radix(size, 16).map_err(|_| invalid("invalid chunk size"))?;
+        if size > MAX_CHUNK_SIZE {
+            return Err(invalid("chunk size too large"));
+        }
+
+        self.data.clear();
+        self.data.resize(size, 0);
+        self.pos = 0;
+        self.reader.read_exact(&mut self.data)?;
+
+        if let Some(signature) = signature {
+            let prev = self.prev_signature();
+            let expected = self.signer.chunk_sign(prev, &self.data);
+            verify(&expected, &signature, "chunk signature mismatch")?;
+            self.prev_signature = Some(expected);
+        }
+        if let Some(checksum) = self.checksum.as_mut() {
+            checksum.update(&self.data);
+        }
+        self.decoded_len += size as u64;
+
+        if size > 0 {
+            let mut crlf = [0u8; 2];
+            self.reader.read_exact(&mut crlf)?;
+            if &crlf != b"\r\n" {
+                return Err(invalid("chunk data not terminated by CRLF"));
+            }
+            return Ok(());
+        }
+
+        self.finished = true;
+        self.read_trailer()?;
+        match self.expected_len {
+            Some(len) if len != self.decoded_len => Err(invalid("decoded length mismatch")),
+            _ => Ok(()),
+        }
+    }
+
+    fn read_trailer(&mut self) -> Result<()> {
+        let checksum = match self.checksum.take() {
+            Some(checksum) => checksum,
+            None => {
+                return match self.read_line()?.as_str() {
+                    "" => Ok(()),
+                    _ => Err(invalid("unexpected trailer")),
+                };
+            }
+        };
+
+        let trailer = self.read_line()?;
+        let (name, value) = trailer
+            .split_once(':')
+            .ok_or_else(|| invalid("invalid trailer"))?;
+        if !name.eq_ignore_ascii_case(checksum.algorithm().header_name()) {
+            return Err(invalid("unexpected trailer"));
+        }
+        let value = value.to_string();
+
+        if self.signed {
+            let line = self.read_line()?;
+            let signature = line
+                .strip_prefix("x-amz-trailer-signature:")
+                .ok_or_else(|| invalid("missing trailer signature"))?;
+            let prev = self.prev_signature();
+            let expected = self.signer.trailer_sign(prev, trailer.clone());
+            verify(&expected, signature, "trailer signature mismatch")?;
+            self.prev_signature = Some(expected);
+        }
+        if !self.read_line()?.is_empty() {
+            return Err(invalid("trailer not terminated"));
+        }
+
+        verify(&checksum.finalize_base64(), &value, "checksum mismatch")
+    }
+}
+
+impl<R: Read, H: Headers> Read for Decoder<R, H> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        while self.pos >= self.data.len() {
+            if self.finished || buf.is_empty() {
+                return Ok(0);
+            }
+            self.next_chunk()?;
+        }
+
+        let len = std::cmp::min(buf.len(), self.data.len() - self.pos);
+        buf[..len].copy_from_slice(&self.data[self.pos..self.pos + len]);
+        self.pos += len;
+        Ok(len)
+    }
+}
+
+fn invalid(msg: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, msg.to_string())
+}
+
+fn verify(expected: &str, actual: &str, msg: &str) -> Result<()> {
+    ring::constant_time::verify_slices_are_equal(expected.as_bytes(), actual.as_bytes())
+        .map_err(|_| invalid(msg))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::aws::example_signer::example_signer;
+    use crate::aws::s3::{self, Holder};
+
+    fn encode(data: &[u8], payload_hash: &str, trailer: Option<ChecksumAlgorithm>) -> Vec<u8> {
+        let mut holder = Holder::new(1000, data, example_signer(payload_hash));
+        if let Some(algorithm) = trailer {
+            holder = holder.with_checksum(algorithm);
+        }
+        if payload_hash == s3::STREAM_UNSIGNED_PAYLOAD_TRAILER {
+            holder = holder.unsigned();
+        }
+        let mut body = Vec::new();
+        holder.read_to_end(&mut body).expect("holder read failed");
+        body
+    }
+
+    fn decode(
+        body: &[u8],
+        payload_hash: &str,
+        trailer: Option<ChecksumAlgorithm>,
+    ) -> Result<Vec<u8>> {
+        let mut decoder = Decoder::new(body, example_signer(payload_hash));
+        if let Some(algorithm) = trailer {
+            decoder = decoder.with_checksum(algorithm);
+        }
+        if payload_hash == s3::STREAM_UNSIGNED_PAYLOAD_TRAILER {
+            decoder = decoder.unsigned();
+        }
+        let mut out = Vec::new();
+        decoder.read_to_end(&mut out)?;
+        Ok(out)
+    }
+
+    #[test]
+    fn decoder_roundtrip() {
+        let data = (0..5000).map(|n| (n % 251) as u8).collect::<Vec<u8>>();
+        let modes = [
+            (s3::STREAM_PAYLOAD, None),
+            (s3::STREAM_PAYLOAD_TRAILER, Some(ChecksumAlgorithm::Crc32c)),
+            (
+                s3::STREAM_UNSIGNED_PAYLOAD_TRAILER,
+                Some(ChecksumAlgorithm::Sha256),
+            ),
+        ];
+        for len in [0, 1, 1000, 5000] {
+            for (payload_hash, trailer) in modes.iter() {
+                let body = encode(&data[..len], payload_hash, *trailer);
+                let decoded = decode(&body, payload_hash, *trailer).expect("decode failed");
+                assert_eq!(decoded, data[..len].to_vec(), "{} {}", len, payload_hash);
+            }
+        }
+    }
+
+    #[test]
+    fn decoder_tampered() {
+        let data = [97u8; 3000];
+        let trailer = Some(ChecksumAlgorithm::Crc32);
+        let body = encode(&data, s3::STREAM_PAYLOAD_TRAILER, trailer);
+
+        //flip a byte inside the second chunk
+        let mut tampered = body.clone();
+        let pos = body.len() / 2;
+        tampered[pos] ^= 1;
+        let err = decode(&tampered, s3::STREAM_PAYLOAD_TRAILER, trailer).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+
+        //the first chunk is still handed out before the tampered one fails
+        let mut decoder = Decoder::new(&tampered[..], example_signer(s3::STREAM_PAYLOAD_TRAILER))
+            .with_checksum(ChecksumAlgorithm::Crc32);
+        let mut buf = [0u8; 1000];
+        decoder.read_exact(&mut buf).expect("first chunk failed");
+        assert!(decoder.read(&mut buf).is_err());
+
+        //wrong checksum in the trailer
+        let text = String::from_utf8(body.clone()).unwrap();
+        let crc = ChecksumAlgorithm::Crc32.checksum().finalize_base64();
+        let idx = text.find("x-amz-checksum-crc32:").unwrap() + "x-amz-checksum-crc32:".len();
+        let forged = format!("{}{}{}", &text[..idx], crc, &text[idx + crc.len()..]);
+        assert!(decode(forged.as_bytes(), s3::STREAM_PAYLOAD_TRAILER, trailer).is_err());
+
+        //a signed body is not accepted as unsigned and vice versa
+        assert!(decode(&body, s3::STREAM_UNSIGNED_PAYLOAD_TRAILER, trailer).is_err());
+        let unsigned = encode(&data, s3::STREAM_UNSIGNED_PAYLOAD_TRAILER, trailer);
+        assert!(decode(&unsigned, s3::STREAM_PAYLOAD_TRAILER, trailer).is_err());
+
+        //truncated body
+        assert!(decode(
+            &body[..body.len() - 10],
+            s3::STREAM_PAYLOAD_TRAILER,
+            trailer
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn decoder_length() {
+        let data = [97u8; 2500];
+        let body = encode(&data, s3::STREAM_PAYLOAD, None);
+        let mut out = Vec::new();
+        let mut decoder =
+            Decoder::new(&body[..], example_signer(s3::STREAM_PAYLOAD)).with_decoded_length(2500);
+        decoder.read_to_end(&mut out).expect("decode failed");
+
+        let mut decoder =
+            Decoder::new(&body[..], example_signer(s3::STREAM_PAYLOAD)).with_decoded_length(2501);
+        assert!(decoder.read_to_end(&mut out).is_err());
+    }
+}
@@ -0,0 +1,248 @@
+//async counterpart of s3::Holder, frames are produced by the same chunked::Encoder
+use crate::{
+    aws::{auth::Sign, checksum::ChecksumAlgorithm, chunked::Encoder},
+    util::Headers,
+};
+use bytes::{Bytes, BytesMut};
+use futures_core::{ready, Stream};
+use std::io::Result;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tokio::io::AsyncRead;
+use tokio_util::io::poll_read_buf;
+
+const READ_BUF_SIZE: usize = 64 * 1024;
+
+//yields one signed aws-chunked frame per item, every chunk but the last
+//carries exactly buf_size bytes of the source
+pub struct AsyncHolder<S, H: Headers> {
+    pub buf_size: usize,
+    source: S,
+    encoder: Encoder<H>,
+    buffer: BytesMut,
+    source_done: bool,
+    finished: bool,
+}
+
+impl<S, H> AsyncHolder<S, H>
+where
+    S: Stream<Item = Result<Bytes>> + Unpin,
+    H: Headers,
+{
+    //buf_size 0 is raised to 1, same as Holder::new
+    pub fn new(buf_size: usize, source: S, signer: Sign<H>) -> Self {
+        let buf_size = buf_size.max(1);
+        Self {
+            buf_size,
+            source,
+            encoder: Encoder::new(signer),
+            buffer: BytesMut::with_capacity(buf_size),
+            source_done: false,
+            finished: false,
+        }
+    }
+
+    //same as Holder::with_checksum
+    pub fn with_checksum(mut self, algorithm: ChecksumAlgorithm) -> Self {
+        self.encoder = self.encoder.with_checksum(algorithm);
+        self
+    }
+
+    //same as Holder::unsigned
+    pub fn unsigned(mut self) -> Self {
+        self.encoder = self.encoder.unsigned();
+        self
+    }
+
+    fn frame(&mut self, len: usize) -> Bytes {
+        let data = self.buffer.split_to(len);
+        let header = self.encoder.chunk_header(&data);
+        let mut frame = BytesMut::with_capacity(header.len() + len + 2);
+        frame.extend_from_slice(header.as_bytes());
+        frame.extend_from_slice(&data);
+        frame.extend_from_slice(b"\r\n");
+        frame.freeze()
+    }
+}
+
+impl<R, H> AsyncHolder<ReaderStream<R>, H>
+where
+    R: AsyncRead + Unpin,
+    H: Headers,
+{
+    pub fn from_async_read(buf_size: usize, reader: R, signer: Sign<H>) -> Self {
+        Self::new(buf_size, ReaderStream::new(reader), signer)
+    }
+}
+
+impl<S, H> Stream for AsyncHolder<S, H>
+where
+    S: Stream<Item = Result<Bytes>> + Unpin,
+    H: Headers + Unpin,
+{
+    type Item = Result<Bytes>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        loop {
+            if this.finished {
+                return Poll::Ready(None);
+            }
+            if this.buffer.len() >= this.buf_size {
+                return Poll::Ready(Some(Ok(this.frame(this.buf_size))));
+            }
+            if this.source_done {
+                if !this.buffer.is_empty() {
+                    return Poll::Ready(Some(Ok(this.frame(this.buffer.len()))));
+                }
+                this.finished = true;
+                return Poll::Ready(Some(Ok(Bytes::from(this.encoder.final_chunk()))));
+            }
+
+            match ready!(Pin::new(&mut this.source).poll_next(cx)) {
+                Some(Ok(bytes)) => this.buffer.extend_from_slice(&bytes),
+                Some(Err(e)) => {
+                    this.finished = true;
+                    return Poll::Ready(Some(Err(e)));
+                }
+                None => this.source_done = true,
+            }
+        }
+    }
+}
+
+//turns an AsyncRead into a stream of Bytes
+pub struct ReaderStream<R> {
+    reader: R,
+    buf: BytesMut,
+    done: bool,
+}
+
+impl<R: AsyncRead + Unpin> ReaderStream<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            buf: BytesMut::new(),
+            done: false,
+        }
+    }
+}
+
+impl<R: AsyncRead + Unpin> Stream for ReaderStream<R> {
+    type Item = Result<Bytes>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        if this.done {
+            return Poll::Ready(None);
+        }
+
+        //reads into the spare capacity, nothing is zero-filled. The
+        //allocation is reused once the previous frames are dropped
+        this.buf.reserve(READ_BUF_SIZE);
+        match ready!(poll_read_buf(Pin::new(&mut this.reader), cx, &mut this.buf)) {
+            Ok(0) => {
+                this.done = true;
+                Poll::Ready(None)
+            }
+            Ok(_) => Poll::Ready(Some(Ok(this.buf.split().freeze()))),
+            Err(e) => {
+                this.done = true;
+                Poll::Ready(Some(Err(e)))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::aws::example_signer::example_signer;
+    use crate::aws::s3::{self, Holder};
+    use std::io::Read;
+    use std::task::Waker;
+
+    //in-memory sources never return Pending
+    fn collect<S: Stream<Item = Result<Bytes>> + Unpin>(mut stream: S) -> Vec<u8> {
+        let mut cx = Context::from_waker(Waker::noop());
+        let mut out = Vec::new();
+        loop {
+            match Pin::new(&mut stream).poll_next(&mut cx) {
+                Poll::Ready(Some(frame)) => out.extend_from_slice(&frame.expect("frame failed")),
+                Poll::Ready(None) => return out,
+                Poll::Pending => panic!("in-memory stream pending"),
+            }
+        }
+    }
+
+    struct Parts(Vec<Bytes>);
+
+    impl Stream for Parts {
+        type Item = Result<Bytes>;
+
+        fn poll_next(mut self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+            match self.0.is_empty() {
+                true => Poll::Ready(None),
+                false => Poll::Ready(Some(Ok(self.0.remove(0)))),
+            }
+        }
+    }
+
+    #[test]
+    fn async_holder_same_bytes() {
+        let data = (0..70_000).map(|n| (n % 253) as u8).collect::<Vec<u8>>();
+        for len in [0, 1, 8192, 70_000] {
+            let data = &data[..len];
+            let mut expected = Vec::new();
+            Holder::new(8192, data, example_signer(s3::STREAM_PAYLOAD_TRAILER))
+                .with_checksum(ChecksumAlgorithm::Crc32)
+                .read_to_end(&mut expected)
+                .expect("holder read failed");
+
+            //uneven parts so chunks are assembled across stream items
+            let parts = data
+                .chunks(3000)
+                .map(Bytes::copy_from_slice)
+                .collect::<Vec<Bytes>>();
+            let stream = AsyncHolder::new(
+                8192,
+                Parts(parts),
+                example_signer(s3::STREAM_PAYLOAD_TRAILER),
+            )
+            .with_checksum(ChecksumAlgorithm::Crc32);
+            assert_eq!(collect(stream), expected, "{}", len);
+
+            let reader = AsyncHolder::from_async_read(
+                8192,
+                data,
+                example_signer(s3::STREAM_PAYLOAD_TRAILER),
+            )
+            .with_checksum(ChecksumAlgorithm::Crc32);
+            assert_eq!(collect(reader), expected, "{}", len);
+        }
+    }
+
+    #[test]
+    fn async_holder_unsigned() {
+        let data = [97u8; 20_000];
+        let mut expected = Vec::new();
+        Holder::new(
+            8192,
+            &data[..],
+            example_signer(s3::STREAM_UNSIGNED_PAYLOAD_TRAILER),
+        )
+        .unsigned()
+        .with_checksum(ChecksumAlgorithm::Sha256)
+        .read_to_end(&mut expected)
+        .expect("holder read failed");
+
+        let stream = AsyncHolder::from_async_read(
+            8192,
+            &data[..],
+            example_signer(s3::STREAM_UNSIGNED_PAYLOAD_TRAILER),
+        )
+        .unsigned()
+        .with_checksum(ChecksumAlgorithm::Sha256);
+        assert_eq!(collect(stream), expected);
+    }
+}
@@ -1,2 +1,9 @@
 pub mod auth;
+pub mod checksum;
+pub mod chunked;
+#[cfg(feature = "aws_async")]
+pub mod chunked_async;
+pub mod eventstream;
+#[cfg(test)]
+pub(crate) mod example_signer;
 pub mod s3;
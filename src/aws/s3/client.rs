@@ -0,0 +1,1538 @@
+use super::endpoint::Endpoint;
+use super::*;
+use crate::progress::{Monitor, Progress, ProgressFn, RateLimiter};
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+use std::io::{Seek, SeekFrom};
+use std::sync::Arc;
+use url::Url;
+
+mod copy;
+mod credentials;
+mod encryption;
+mod error;
+mod multipart;
+mod parallel;
+mod post_policy;
+mod presign;
+mod resumable;
+mod retry;
+mod sse;
+pub use copy::*;
+pub use credentials::*;
+pub use encryption::*;
+pub use error::*;
+pub use multipart::*;
+pub use parallel::*;
+pub use post_policy::*;
+pub use presign::*;
+pub use resumable::*;
+pub use retry::*;
+pub use sse::*;
+
+type MHeader = HashMap<String, String>;
+//moves a source back to where the body starts
+type Rewind<'a, T> = &'a dyn Fn(&mut T) -> Result<()>;
+
+pub struct Client {
+    region: String,
+    credentials: Arc<dyn CredentialsProvider>,
+    endpoint: Endpoint,
+    progress: Option<ProgressFn>,
+    limiter: Option<RateLimiter>,
+    multipart_threshold: u64,
+    part_size: usize,
+    retry: RetryPolicy,
+    encryption: Option<MasterKey>,
+}
+
+pub struct PutObjectInput<T: Read> {
+    pub bucket: String,
+    pub key: String,
+    pub content_len: String,
+    pub data: T,
+    pub options: ObjectOptions,
+}
+
+impl<T: Read> PutObjectInput<T> {
+    pub fn new(bucket: &str, key: &str, content_len: u64, data: T) -> Self {
+        Self {
+            bucket: bucket.to_string(),
+            key: key.to_string(),
+            content_len: content_len.to_string(),
+            data,
+            options: ObjectOptions::default(),
+        }
+    }
+
+    pub fn with_options(mut self, options: ObjectOptions) -> Self {
+        self.options = options;
+        self
+    }
+}
+
+//headers stored with a new object, sent with PutObject and
+//CreateMultipartUpload and covered by the signature
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ObjectOptions {
+    pub content_type: Option<String>,
+    pub cache_control: Option<String>,
+    pub content_disposition: Option<String>,
+    //x-amz-meta-* without the prefix
+    pub metadata: HashMap<String, String>,
+    pub storage_class: Option<StorageClass>,
+    pub acl: Option<CannedAcl>,
+    //x-amz-tagging, tag keys and values
+    pub tagging: HashMap<String, String>,
+    pub encryption: Option<ServerSideEncryption>,
+}
+
+impl ObjectOptions {
+    fn headers(&self) -> MHeader {
+        let mut headers = self
+            .metadata
+            .iter()
+            .map(|(k, v)| (format!("x-amz-meta-{}", k), v.clone()))
+            .collect::<MHeader>();
+        let mut set = |name: &str, value: Option<&str>| {
+            if let Some(value) = value {
+                headers.insert(name.to_string(), value.to_string());
+            }
+        };
+        set("Content-Type", self.content_type.as_deref());
+        set("Cache-Control", self.cache_control.as_deref());
+        set("Content-Disposition", self.content_disposition.as_deref());
+        set(
+            "x-amz-storage-class",
+            self.storage_class.map(|c| c.as_str()),
+        );
+        set("x-amz-acl", self.acl.map(|a| a.as_str()));
+        if !self.tagging.is_empty() {
+            let mut tags = self
+                .tagging
+                .iter()
+                .map(|(k, v)| (k.clone(), v.clone()))
+                .collect::<Vec<(String, String)>>();
+            tags.sort();
+            headers.insert("x-amz-tagging".to_string(), query_string(&tags));
+        }
+        if let Some(encryption) = &self.encryption {
+            headers.extend(encryption.headers());
+        }
+        headers
+    }
+
+    //headers repeated with every UploadPart
+    fn part_headers(&self) -> MHeader {
+        self.encryption
+            .as_ref()
+            .map(|e| e.part_headers())
+            .unwrap_or_default()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StorageClass {
+    Standard,
+    ReducedRedundancy,
+    StandardIa,
+    OnezoneIa,
+    IntelligentTiering,
+    Glacier,
+    GlacierIr,
+    DeepArchive,
+}
+
+impl StorageClass {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Standard => "STANDARD",
+            Self::ReducedRedundancy => "REDUCED_REDUNDANCY",
+            Self::StandardIa => "STANDARD_IA",
+            Self::OnezoneIa => "ONEZONE_IA",
+            Self::IntelligentTiering => "INTELLIGENT_TIERING",
+            Self::Glacier => "GLACIER",
+            Self::GlacierIr => "GLACIER_IR",
+            Self::DeepArchive => "DEEP_ARCHIVE",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CannedAcl {
+    Private,
+    PublicRead,
+    PublicReadWrite,
+    AuthenticatedRead,
+    AwsExecRead,
+    BucketOwnerRead,
+    BucketOwnerFullControl,
+}
+
+impl CannedAcl {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Private => "private",
+            Self::PublicRead => "public-read",
+            Self::PublicReadWrite => "public-read-write",
+            Self::AuthenticatedRead => "authenticated-read",
+            Self::AwsExecRead => "aws-exec-read",
+            Self::BucketOwnerRead => "bucket-owner-read",
+            Self::BucketOwnerFullControl => "bucket-owner-full-control",
+        }
+    }
+}
+
+impl Client {
+    pub fn new(region: String) -> Self {
+        Client {
+            region,
+            credentials: Arc::new(EnvCredentials),
+            endpoint: Endpoint::default(),
+            progress: None,
+            limiter: None,
+            multipart_threshold: DEFAULT_MULTIPART_THRESHOLD,
+            part_size: DEFAULT_PART_SIZE,
+            retry: RetryPolicy::default(),
+            encryption: None,
+        }
+    }
+
+    //encrypt objects on the host before they are sent, get_object decrypts
+    //them again. The whole object is encrypted at once, so only put_object
+    //and upload below the multipart threshold accept encrypted objects
+    pub fn with_encryption(mut self, master_key: MasterKey) -> Self {
+        self.encryption = Some(master_key);
+        self
+    }
+
+    //stream and multipart uploads can not be encrypted
+    fn check_unencrypted(&self) -> S3Result<()> {
+        match self.encryption {
+            Some(_) => Err(S3Error::other(
+                "client-side encryption needs the whole object, use put_object",
+            )),
+            None => Ok(()),
+        }
+    }
+
+    //presigned requests go to S3 directly, past the encryption
+    fn check_presignable(&self) -> S3Result<()> {
+        match self.encryption {
+            Some(_) => Err(S3Error::other(
+                "client-side encryption can not be applied to presigned requests",
+            )),
+            None => Ok(()),
+        }
+    }
+
+    //retries of failed requests, stream uploads are only retried through
+    //put_object_seekable
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    //EnvCredentials by default, the provider is asked for every request
+    pub fn with_credentials<P>(mut self, provider: P) -> Self
+    where
+        P: CredentialsProvider + 'static,
+    {
+        self.credentials = Arc::new(provider);
+        self
+    }
+
+    //https to the regional AWS host by default
+    pub fn with_endpoint(mut self, endpoint: Endpoint) -> Self {
+        self.endpoint = endpoint;
+        self
+    }
+
+    //upload switches to multipart at threshold bytes, part_size is raised
+    //to MIN_PART_SIZE and as needed to stay within MAX_PARTS
+    pub fn with_multipart(mut self, threshold: u64, part_size: usize) -> Self {
+        self.multipart_threshold = threshold;
+        self.part_size = part_size.max(MIN_PART_SIZE);
+        self
+    }
+
+    //report request body bytes sent for every upload
+    pub fn with_progress<F>(mut self, progress: F) -> Self
+    where
+        F: Fn(Progress) + Send + Sync + 'static,
+    {
+        self.progress = Some(Arc::new(progress));
+        self
+    }
+
+    //limit upload bandwidth, shared by every upload of this client
+    pub fn with_rate_limit(mut self, bytes_per_sec: u64) -> Self {
+        self.limiter = Some(RateLimiter::new(bytes_per_sec));
+        self
+    }
+
+    fn monitor<R: Read>(&self, body: R, total: u64) -> Monitor<R> {
+        Monitor::new(body, Some(total))
+            .with_progress(self.progress.clone())
+            .with_rate_limit(self.limiter.clone())
+    }
+
+    fn make_signer(
+        &self,
+        method: &str,
+        bucket: &str,
+        key: &str,
+        query: &[(String, String)],
+        mode: Transfer,
+        extra: MHeader,
+    ) -> S3Result<(Sign<MHeader>, MHeader, String)> {
+        let credentials = self.credentials.credentials()?;
+        let date = chrono::Utc::now();
+
+        let (host, mut full_url) = self.endpoint.url(&self.region, bucket, key)?;
+        if !query.is_empty() {
+            full_url.push('?');
+            full_url.push_str(&query_string(query));
+        }
+
+        let mut headers = HashMap::new();
+        headers.insert("Host".to_string(), host);
+        headers.insert("x-amz-content-sha256".to_string(), mode.payload_hash());
+        headers.insert(
+            "x-amz-date".to_string(),
+            date.format(util::LONG_DATETIME).to_string(),
+        );
+        headers.extend(mode.extra_headers());
+        headers.extend(extra);
+        if let Some(token) = credentials.session_token {
+            headers.insert("X-Amz-Security-Token".to_string(), token);
+        }
+
+        let signer = Sign {
+            service: "s3".to_string(),
+            method: method.to_string(),
+            url: Url::parse(&full_url)
+                .map_err(|e| S3Error::other(format!("invalid url {}: {}", full_url, e)))?,
+            datetime: date,
+            region: self.region.clone(),
+            access_key: credentials.access_key,
+            secret_key: credentials.secret_key,
+            headers: headers.clone(),
+            hash_request_payload: mode.payload_hash(),
+        };
+
+        headers.insert("Authorization".to_string(), signer.sign());
+
+        Ok((signer, headers, full_url))
+    }
+
+    pub fn put_object<T: Read>(&self, mut input: PutObjectInput<T>) -> S3Result<()> {
+        let mut buf = Vec::new();
+        input
+            .data
+            .read_to_end(&mut buf)
+            .map_err(|_| S3Error::other("input data read err"))?;
+        let mut extra = input.options.headers();
+        if let Some(master_key) = &self.encryption {
+            let (encrypted, envelope) = master_key.encrypt(buf)?;
+            buf = encrypted;
+            extra.extend(envelope);
+        }
+        let len = buf.len() as u64;
+
+        self.retry.run("Put object", |_| {
+            let mut extra = extra.clone();
+            extra.insert("Content-Length".to_string(), len.to_string());
+            let (_, headers, full_url) = self.make_signer(
+                "PUT",
+                &input.bucket,
+                &input.key,
+                &[],
+                Transfer::Single,
+                extra,
+            )?;
+
+            let mut request = ureq::put(&full_url);
+            for (k, v) in headers {
+                request = request.set(&k, &v);
+            }
+            check_response(request.send(self.monitor(std::io::Cursor::new(&buf), len)))?;
+            Ok(())
+        })
+    }
+
+    pub fn put_object_stream<T: Read>(
+        &self,
+        chunk_kb: usize,
+        input: PutObjectInput<T>,
+    ) -> S3Result<()> {
+        let mode = Transfer::Multiple(input.content_len.clone());
+        self.send_stream(chunk_kb, input, mode, None)
+    }
+
+    //stream upload with a checksum trailer, S3 verifies the checksum
+    //against the received data and rejects the object on mismatch
+    pub fn put_object_stream_checksum<T: Read>(
+        &self,
+        chunk_kb: usize,
+        input: PutObjectInput<T>,
+        algorithm: ChecksumAlgorithm,
+    ) -> S3Result<()> {
+        let mode = Transfer::MultipleTrailer(input.content_len.clone(), algorithm);
+        self.send_stream(chunk_kb, input, mode, None)
+    }
+
+    //upload with an explicit transfer mode, chunk_kb is ignored for Single
+    pub fn put_object_mode<T: Read>(
+        &self,
+        chunk_kb: usize,
+        input: PutObjectInput<T>,
+        mode: Transfer,
+    ) -> S3Result<()> {
+        match mode {
+            Transfer::Single => self.put_object(input),
+            _ => self.send_stream(chunk_kb, input, mode, None),
+        }
+    }
+
+    //put_object_mode for seekable sources, a failed attempt seeks back to
+    //the position the source had on entry and is retried
+    pub fn put_object_seekable<T: Read + Seek>(
+        &self,
+        chunk_kb: usize,
+        mut input: PutObjectInput<T>,
+        mode: Transfer,
+    ) -> S3Result<()> {
+        if let Transfer::Single = mode {
+            return self.put_object(input);
+        }
+        let start = input
+            .data
+            .stream_position()
+            .map_err(|e| S3Error::other(format!("input data seek err: {}", e)))?;
+        let rewind = move |data: &mut T| data.seek(SeekFrom::Start(start)).map(|_| ());
+        self.send_stream(chunk_kb, input, mode, Some(&rewind))
+    }
+
+    //without rewind the body can not be sent twice and a failure is final
+    fn send_stream<T: Read>(
+        &self,
+        chunk_kb: usize,
+        input: PutObjectInput<T>,
+        mode: Transfer,
+        rewind: Option<Rewind<'_, T>>,
+    ) -> S3Result<()> {
+        self.check_unencrypted()?;
+        //an empty chunk would end the body before any data
+        if chunk_kb == 0 {
+            return Err(S3Error::other("chunk size must be at least 1 KB"));
+        }
+        let checksum = mode.checksum();
+        let signed = mode.signed_chunks();
+        let decoded_len = input
+            .content_len
+            .parse::<u64>()
+            .map_err(|_| S3Error::other("invalid content length"))?;
+        let chunk_size = chunk_kb * 1024;
+        let content_len = aws_chunked_length(decoded_len, chunk_size, signed, checksum);
+        let PutObjectInput {
+            bucket,
+            key,
+            mut data,
+            options,
+            ..
+        } = input;
+        let retry = match rewind {
+            Some(_) => self.retry.clone(),
+            None => RetryPolicy::none(),
+        };
+
+        let result = retry.run("Put object stream", |attempt| {
+            if let (true, Some(rewind)) = (attempt > 0, rewind) {
+                rewind(&mut data)
+                    .map_err(|e| S3Error::other(format!("input data seek err: {}", e)))?;
+            }
+            let mut extra = options.headers();
+            extra.insert("Content-Length".to_string(), content_len.to_string());
+            let (signer, headers, full_url) =
+                self.make_signer("PUT", &bucket, &key, &[], mode.clone(), extra)?;
+            let mut holder = Holder::new(chunk_size, &mut data, signer);
+            if let Some(algorithm) = checksum {
+                holder = holder.with_checksum(algorithm);
+            }
+            if !signed {
+                holder = holder.unsigned();
+            }
+            let mut request = ureq::put(&full_url);
+            for (k, v) in headers {
+                request = request.set(&k, &v);
+            }
+
+            let resp = check_response(request.send(self.monitor(holder, content_len)))?;
+            log::debug!("Response ok {}", resp.into_string().unwrap_or_default());
+            Ok(())
+        });
+        if let (Err(e), None) = (&result, rewind) {
+            if e.is_retryable() {
+                log::info!("Stream body can not be replayed, not retrying: {}", e);
+            }
+        }
+        result
+    }
+
+    //the body is streamed from the connection, read it to the end
+    //or drop it to close the connection
+    pub fn get_object(&self, input: GetObjectInput) -> S3Result<GetObjectOutput> {
+        let resp = self.retry.run("Get object", |_| {
+            let (_, headers, full_url) = self.make_signer(
+                "GET",
+                &input.bucket,
+                &input.key,
+                &input.query(),
+                Transfer::Single,
+                input.headers(),
+            )?;
+
+            let mut request = ureq::get(&full_url);
+            for (k, v) in headers {
+                request = request.set(&k, &v);
+            }
+            check_response(request.call())
+        })?;
+        let mut metadata = ObjectMetadata::from_response(&resp);
+        let mut body: Box<dyn Read + Send> = Box::new(resp.into_reader());
+        if let Some(master_key) = &self.encryption {
+            if encryption::is_encrypted(&metadata.metadata) {
+                //the tag at the end authenticates the whole object
+                if input.range.is_some() {
+                    return Err(S3Error::other(
+                        "ranged reads of client-side encrypted objects are not supported",
+                    ));
+                }
+                let mut data = Vec::new();
+                body.read_to_end(&mut data)
+                    .map_err(|e| S3Error::Transport(format!("read response failed: {}", e)))?;
+                let data = master_key.decrypt(&metadata.metadata, data)?;
+                metadata.content_length = Some(data.len() as u64);
+                body = Box::new(std::io::Cursor::new(data));
+            }
+        }
+        Ok(GetObjectOutput { metadata, body })
+    }
+
+    //same request options as get_object, only the metadata is returned
+    pub fn head_object(&self, input: GetObjectInput) -> S3Result<HeadObjectOutput> {
+        self.retry.run("Head object", |_| {
+            let (_, headers, full_url) = self.make_signer(
+                "HEAD",
+                &input.bucket,
+                &input.key,
+                &input.query(),
+                Transfer::Single,
+                input.headers(),
+            )?;
+
+            let mut request = ureq::head(&full_url);
+            for (k, v) in headers {
+                request = request.set(&k, &v);
+            }
+            head_output(request.call())
+        })
+    }
+
+    //without s3:ListBucket permission S3 answers 403 for missing keys,
+    //which is reported as an error
+    pub fn object_exists(&self, bucket: &str, key: &str) -> S3Result<bool> {
+        let output = self.head_object(GetObjectInput::new(bucket, key))?;
+        Ok(output.exists())
+    }
+
+    pub fn delete_object(
+        &self,
+        bucket: &str,
+        key: &str,
+        version_id: Option<&str>,
+    ) -> S3Result<DeleteObjectOutput> {
+        let query = version_id
+            .map(|v| vec![("versionId".to_string(), v.to_string())])
+            .unwrap_or_default();
+        let resp = self.retry.run("Delete object", |_| {
+            let (_, headers, full_url) = self.make_signer(
+                "DELETE",
+                bucket,
+                key,
+                &query,
+                Transfer::Single,
+                HashMap::new(),
+            )?;
+
+            let mut request = ureq::delete(&full_url);
+            for (k, v) in headers {
+                request = request.set(&k, &v);
+            }
+            check_response(request.call())
+        })?;
+        Ok(DeleteObjectOutput {
+            delete_marker: resp.header("x-amz-delete-marker") == Some("true"),
+            version_id: resp.header("x-amz-version-id").map(|v| v.to_string()),
+        })
+    }
+
+    //one request per DELETE_BATCH objects, results of all batches are merged,
+    //in quiet mode only the failed keys are reported
+    pub fn delete_objects(
+        &self,
+        bucket: &str,
+        objects: &[ObjectIdentifier],
+        quiet: bool,
+    ) -> S3Result<DeleteObjectsOutput> {
+        let mut output = DeleteObjectsOutput::default();
+        for batch in objects.chunks(DELETE_BATCH) {
+            let body = delete_objects_body(batch, quiet);
+            let mut extra = HashMap::new();
+            extra.insert("Content-Type".to_string(), "application/xml".to_string());
+
+            let query = vec![("delete".to_string(), "".to_string())];
+            let resp = self.send_bytes("POST", bucket, "", &query, extra, body.as_bytes())?;
+            let resp_body = resp
+                .into_string()
+                .map_err(|e| S3Error::other(format!("read response failed: {}", e)))?;
+            let result = DeleteObjectsOutput::from_xml(&resp_body)?;
+            output.deleted.extend(result.deleted);
+            output.errors.extend(result.errors);
+        }
+        Ok(output)
+    }
+
+    //one page of at most max_keys entries, see list_objects_v2_pages
+    pub fn list_objects_v2(&self, input: &ListObjectsInput) -> S3Result<ListObjectsOutput> {
+        let text = self.get_string(&input.bucket, "", &input.query())?;
+        ListObjectsOutput::from_xml(&text)
+    }
+
+    //follows NextContinuationToken until the listing is complete
+    pub fn list_objects_v2_pages(&self, input: ListObjectsInput) -> ListObjectsPages<'_> {
+        ListObjectsPages {
+            client: self,
+            input,
+            done: false,
+        }
+    }
+
+    //small request body sent in one piece with Content-MD5, retried
+    fn send_bytes(
+        &self,
+        method: &str,
+        bucket: &str,
+        key: &str,
+        query: &[(String, String)],
+        extra: MHeader,
+        body: &[u8],
+    ) -> S3Result<ureq::Response> {
+        self.retry.run(method, |_| {
+            self.try_send_bytes(method, bucket, key, query, extra.clone(), body)
+        })
+    }
+
+    //single attempt of send_bytes, for callers retrying around the response
+    fn try_send_bytes(
+        &self,
+        method: &str,
+        bucket: &str,
+        key: &str,
+        query: &[(String, String)],
+        mut extra: MHeader,
+        body: &[u8],
+    ) -> S3Result<ureq::Response> {
+        extra.insert("Content-Length".to_string(), body.len().to_string());
+        extra.insert("Content-MD5".to_string(), util::md5(body));
+        let (_, headers, full_url) =
+            self.make_signer(method, bucket, key, query, Transfer::Single, extra)?;
+
+        let mut request = ureq::request(method, &full_url);
+        for (k, v) in headers {
+            request = request.set(&k, &v);
+        }
+        check_response(request.send_bytes(body))
+    }
+
+    fn get_string(&self, bucket: &str, key: &str, query: &[(String, String)]) -> S3Result<String> {
+        self.retry.run("GET", |_| {
+            let (_, headers, full_url) =
+                self.make_signer("GET", bucket, key, query, Transfer::Single, HashMap::new())?;
+
+            let mut request = ureq::get(&full_url);
+            for (k, v) in headers {
+                request = request.set(&k, &v);
+            }
+            check_response(request.call())?
+                .into_string()
+                .map_err(|e| S3Error::Transport(format!("read response failed: {}", e)))
+        })
+    }
+}
+
+//limit of a single DeleteObjects request
+pub const DELETE_BATCH: usize = 1000;
+
+fn delete_objects_body(objects: &[ObjectIdentifier], quiet: bool) -> String {
+    let mut body = String::from("<Delete>");
+    if quiet {
+        body.push_str("<Quiet>true</Quiet>");
+    }
+    for object in objects {
+        body.push_str("<Object><Key>");
+        body.push_str(&xml_escape(&object.key));
+        body.push_str("</Key>");
+        if let Some(version_id) = &object.version_id {
+            body.push_str("<VersionId>");
+            body.push_str(&xml_escape(version_id));
+            body.push_str("</VersionId>");
+        }
+        body.push_str("</Object>");
+    }
+    body.push_str("</Delete>");
+    body
+}
+
+fn xml_escape(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => result.push_str("&amp;"),
+            '<' => result.push_str("&lt;"),
+            '>' => result.push_str("&gt;"),
+            '"' => result.push_str("&quot;"),
+            '\'' => result.push_str("&apos;"),
+            '\r' => result.push_str("&#13;"),
+            '\n' => result.push_str("&#10;"),
+            _ => result.push(c),
+        }
+    }
+    result
+}
+
+//JSON object of string pairs, sorted by key
+fn json_object(pairs: &HashMap<String, String>) -> String {
+    let mut pairs = pairs.iter().collect::<Vec<(&String, &String)>>();
+    pairs.sort();
+    let fields = pairs
+        .into_iter()
+        .map(|(k, v)| format!("{}:{}", json_string(k), json_string(v)))
+        .collect::<Vec<String>>();
+    format!("{{{}}}", fields.join(","))
+}
+
+fn json_string(text: &str) -> String {
+    let mut result = String::with_capacity(text.len() + 2);
+    result.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => result.push_str("\\\""),
+            '\\' => result.push_str("\\\\"),
+            '\n' => result.push_str("\\n"),
+            '\r' => result.push_str("\\r"),
+            '\t' => result.push_str("\\t"),
+            c if (c as u32) < 0x20 => result.push_str(&format!("\\u{:04x}", c as u32)),
+            c => result.push(c),
+        }
+    }
+    result.push('"');
+    result
+}
+
+fn parse_xml(text: &str) -> S3Result<roxmltree::Document<'_>> {
+    roxmltree::Document::parse(text)
+        .map_err(|e| S3Error::other(format!("invalid response xml: {}", e)))
+}
+
+//text of the first child element with the given name, namespaces are ignored
+fn child_text(node: roxmltree::Node, name: &str) -> Option<String> {
+    node.children()
+        .find(|n| n.is_element() && n.tag_name().name() == name)
+        .map(|n| n.text().unwrap_or_default().to_string())
+}
+
+fn head_output(
+    result: std::result::Result<ureq::Response, ureq::Error>,
+) -> S3Result<HeadObjectOutput> {
+    match result {
+        Err(ureq::Error::Status(404, _)) => Ok(HeadObjectOutput::NotFound),
+        result => {
+            let resp = check_response(result)?;
+            Ok(HeadObjectOutput::Found(ObjectMetadata::from_response(
+                &resp,
+            )))
+        }
+    }
+}
+
+fn check_response(
+    result: std::result::Result<ureq::Response, ureq::Error>,
+) -> S3Result<ureq::Response> {
+    use ureq::Error;
+    match result {
+        //ureq does not treat 304 as an error
+        Ok(resp) if resp.status() == 304 => {
+            let mut error = ServiceError::from_response(resp);
+            error.code = "NotModified".to_string();
+            Err(S3Error::from(error))
+        }
+        Ok(resp) => Ok(resp),
+        Err(Error::Status(code, resp)) => {
+            let error = ServiceError::from_response(resp);
+            log::info!("Error status {} {:?}", code, error);
+            Err(S3Error::from(error))
+        }
+        Err(trans) => {
+            use ureq::ErrorKind::*;
+            log::info!("Transport failed {:?}", trans);
+            //invalid urls, redirect loops and proxy auth fail the same way again
+            match trans.kind() {
+                Dns | ConnectionFailed | BadStatus | BadHeader | Io | ProxyConnect => {
+                    Err(S3Error::Transport(trans.to_string()))
+                }
+                _ => Err(S3Error::other(format!("Http failed {}", trans))),
+            }
+        }
+    }
+}
+
+fn query_string(query: &[(String, String)]) -> String {
+    query
+        .iter()
+        .map(|(k, v)| {
+            format!(
+                "{}={}",
+                util::uri_encode(k, true),
+                util::uri_encode(v, true)
+            )
+        })
+        .collect::<Vec<String>>()
+        .join("&")
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct GetObjectInput {
+    pub bucket: String,
+    pub key: String,
+    pub version_id: Option<String>,
+    //Range header value, e.g. "bytes=0-99"
+    pub range: Option<String>,
+    pub if_match: Option<String>,
+    pub if_none_match: Option<String>,
+    pub if_modified_since: Option<DateTime<Utc>>,
+    pub if_unmodified_since: Option<DateTime<Utc>>,
+    //response-* overrides of the returned headers
+    pub response_content_type: Option<String>,
+    pub response_content_language: Option<String>,
+    pub response_expires: Option<String>,
+    pub response_cache_control: Option<String>,
+    pub response_content_disposition: Option<String>,
+    pub response_content_encoding: Option<String>,
+    //required to read objects stored with SSE-C
+    pub sse_customer_key: Option<CustomerKey>,
+}
+
+impl GetObjectInput {
+    pub fn new(bucket: &str, key: &str) -> Self {
+        Self {
+            bucket: bucket.to_string(),
+            key: key.to_string(),
+            ..Default::default()
+        }
+    }
+
+    //inclusive byte range, an open end reads to the end of the object
+    pub fn with_range(mut self, start: u64, end: Option<u64>) -> Self {
+        self.range = Some(match end {
+            Some(end) => format!("bytes={}-{}", start, end),
+            None => format!("bytes={}-", start),
+        });
+        self
+    }
+
+    //last n bytes of the object
+    pub fn with_suffix_range(mut self, len: u64) -> Self {
+        self.range = Some(format!("bytes=-{}", len));
+        self
+    }
+
+    fn headers(&self) -> MHeader {
+        let mut headers = HashMap::new();
+        let mut set = |name: &str, value: Option<String>| {
+            if let Some(value) = value {
+                headers.insert(name.to_string(), value);
+            }
+        };
+        set("Range", self.range.clone());
+        set("If-Match", self.if_match.clone());
+        set("If-None-Match", self.if_none_match.clone());
+        set(
+            "If-Modified-Since",
+            self.if_modified_since
+                .map(|d| d.format(util::GMT_DATETIME).to_string()),
+        );
+        set(
+            "If-Unmodified-Since",
+            self.if_unmodified_since
+                .map(|d| d.format(util::GMT_DATETIME).to_string()),
+        );
+        if let Some(key) = &self.sse_customer_key {
+            headers.extend(key.headers(SSE_CUSTOMER));
+        }
+        headers
+    }
+
+    fn query(&self) -> Vec<(String, String)> {
+        [
+            ("response-cache-control", &self.response_cache_control),
+            (
+                "response-content-disposition",
+                &self.response_content_disposition,
+            ),
+            ("response-content-encoding", &self.response_content_encoding),
+            ("response-content-language", &self.response_content_language),
+            ("response-content-type", &self.response_content_type),
+            ("response-expires", &self.response_expires),
+            ("versionId", &self.version_id),
+        ]
+        .iter()
+        .filter_map(|(k, v)| v.as_ref().map(|v| (k.to_string(), v.clone())))
+        .collect()
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ObjectMetadata {
+    pub etag: Option<String>,
+    pub content_length: Option<u64>,
+    pub content_type: Option<String>,
+    //set for ranged reads, e.g. "bytes 0-99/1000"
+    pub content_range: Option<String>,
+    pub last_modified: Option<DateTime<Utc>>,
+    pub version_id: Option<String>,
+    //x-amz-meta-* headers with the prefix removed
+    pub metadata: HashMap<String, String>,
+}
+
+impl ObjectMetadata {
+    fn from_response(resp: &ureq::Response) -> Self {
+        let header = |name: &str| resp.header(name).map(|v| v.to_string());
+        let metadata = resp
+            .headers_names()
+            .into_iter()
+            .filter_map(|name| {
+                let lower = name.to_lowercase();
+                let key = lower.strip_prefix("x-amz-meta-")?.to_string();
+                resp.header(&name).map(|v| (key, v.to_string()))
+            })
+            .collect();
+
+        Self {
+            etag: header("ETag"),
+            content_length: resp.header("Content-Length").and_then(|v| v.parse().ok()),
+            content_type: header("Content-Type"),
+            content_range: header("Content-Range"),
+            last_modified: resp
+                .header("Last-Modified")
+                .and_then(|v| DateTime::parse_from_rfc2822(v).ok())
+                .map(|d| d.with_timezone(&Utc)),
+            version_id: header("x-amz-version-id"),
+            metadata,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum HeadObjectOutput {
+    Found(ObjectMetadata),
+    NotFound,
+}
+
+impl HeadObjectOutput {
+    pub fn exists(&self) -> bool {
+        matches!(self, Self::Found(_))
+    }
+
+    pub fn metadata(&self) -> Option<&ObjectMetadata> {
+        match self {
+            Self::Found(metadata) => Some(metadata),
+            Self::NotFound => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DeleteObjectOutput {
+    //set when versioning created a delete marker
+    pub delete_marker: bool,
+    pub version_id: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ObjectIdentifier {
+    pub key: String,
+    pub version_id: Option<String>,
+}
+
+impl ObjectIdentifier {
+    pub fn new(key: &str) -> Self {
+        Self {
+            key: key.to_string(),
+            version_id: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DeletedObject {
+    pub key: String,
+    pub version_id: Option<String>,
+    pub delete_marker: bool,
+    pub delete_marker_version_id: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DeleteError {
+    pub key: String,
+    pub version_id: Option<String>,
+    pub code: String,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DeleteObjectsOutput {
+    pub deleted: Vec<DeletedObject>,
+    pub errors: Vec<DeleteError>,
+}
+
+impl DeleteObjectsOutput {
+    fn from_xml(text: &str) -> S3Result<Self> {
+        let doc = parse_xml(text)?;
+        let root = doc.root_element();
+        if root.tag_name().name() != "DeleteResult" {
+            return Err(unexpected_body(text));
+        }
+
+        let mut output = Self::default();
+        for node in root.children().filter(|n| n.is_element()) {
+            match node.tag_name().name() {
+                "Deleted" => output.deleted.push(DeletedObject {
+                    key: child_text(node, "Key").unwrap_or_default(),
+                    version_id: child_text(node, "VersionId"),
+                    delete_marker: child_text(node, "DeleteMarker").as_deref() == Some("true"),
+                    delete_marker_version_id: child_text(node, "DeleteMarkerVersionId"),
+                }),
+                "Error" => output.errors.push(DeleteError {
+                    key: child_text(node, "Key").unwrap_or_default(),
+                    version_id: child_text(node, "VersionId"),
+                    code: child_text(node, "Code").unwrap_or_default(),
+                    message: child_text(node, "Message").unwrap_or_default(),
+                }),
+                _ => (),
+            }
+        }
+        Ok(output)
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ListObjectsInput {
+    pub bucket: String,
+    pub prefix: Option<String>,
+    pub delimiter: Option<String>,
+    pub start_after: Option<String>,
+    //page size, S3 returns at most 1000
+    pub max_keys: Option<u32>,
+    pub continuation_token: Option<String>,
+}
+
+impl ListObjectsInput {
+    pub fn new(bucket: &str) -> Self {
+        Self {
+            bucket: bucket.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn query(&self) -> Vec<(String, String)> {
+        let max_keys = self.max_keys.map(|n| n.to_string());
+        let mut query = vec![("list-type".to_string(), "2".to_string())];
+        query.extend(
+            [
+                ("continuation-token", &self.continuation_token),
+                ("delimiter", &self.delimiter),
+                ("max-keys", &max_keys),
+                ("prefix", &self.prefix),
+                ("start-after", &self.start_after),
+            ]
+            .iter()
+            .filter_map(|(k, v)| v.as_ref().map(|v| (k.to_string(), v.clone()))),
+        );
+        query
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Object {
+    pub key: String,
+    pub last_modified: Option<DateTime<Utc>>,
+    pub etag: Option<String>,
+    pub size: u64,
+    pub storage_class: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CommonPrefix {
+    pub prefix: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ListObjectsOutput {
+    pub objects: Vec<Object>,
+    //keys rolled up by the delimiter
+    pub common_prefixes: Vec<CommonPrefix>,
+    pub is_truncated: bool,
+    pub next_continuation_token: Option<String>,
+    pub key_count: u64,
+}
+
+impl ListObjectsOutput {
+    fn from_xml(text: &str) -> S3Result<Self> {
+        let doc = parse_xml(text)?;
+        let root = doc.root_element();
+        if root.tag_name().name() != "ListBucketResult" {
+            return Err(unexpected_body(text));
+        }
+
+        let mut output = Self {
+            is_truncated: child_text(root, "IsTruncated").as_deref() == Some("true"),
+            next_continuation_token: child_text(root, "NextContinuationToken"),
+            key_count: child_text(root, "KeyCount")
+                .and_then(|v| v.parse().ok())
+                .unwrap_or_default(),
+            ..Default::default()
+        };
+        for node in root.children().filter(|n| n.is_element()) {
+            match node.tag_name().name() {
+                "Contents" => output.objects.push(Object {
+                    key: child_text(node, "Key").unwrap_or_default(),
+                    last_modified: child_text(node, "LastModified")
+                        .and_then(|v| DateTime::parse_from_rfc3339(&v).ok())
+                        .map(|d| d.with_timezone(&Utc)),
+                    etag: child_text(node, "ETag"),
+                    size: child_text(node, "Size")
+                        .and_then(|v| v.parse().ok())
+                        .unwrap_or_default(),
+                    storage_class: child_text(node, "StorageClass"),
+                }),
+                "CommonPrefixes" => output.common_prefixes.push(CommonPrefix {
+                    prefix: child_text(node, "Prefix").unwrap_or_default(),
+                }),
+                _ => (),
+            }
+        }
+        Ok(output)
+    }
+}
+
+//iterator over ListObjectsV2 pages, stops after the first error
+pub struct ListObjectsPages<'a> {
+    client: &'a Client,
+    input: ListObjectsInput,
+    done: bool,
+}
+
+impl<'a> Iterator for ListObjectsPages<'a> {
+    type Item = S3Result<ListObjectsOutput>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let page = self.client.list_objects_v2(&self.input);
+        match &page {
+            Ok(output) if output.is_truncated => match &output.next_continuation_token {
+                Some(token) => self.input.continuation_token = Some(token.clone()),
+                None => self.done = true,
+            },
+            _ => self.done = true,
+        }
+        Some(page)
+    }
+}
+
+pub struct GetObjectOutput {
+    pub metadata: ObjectMetadata,
+    pub body: Box<dyn Read + Send + 'static>,
+}
+
+pub type ContentLength = String;
+#[derive(Clone)]
+pub enum Transfer {
+    //single request with UNSIGNED-PAYLOAD
+    Single,
+    //aws-chunked with signed chunks
+    Multiple(ContentLength),
+    //aws-chunked with signed chunks and a signed checksum trailer
+    MultipleTrailer(ContentLength, ChecksumAlgorithm),
+    //aws-chunked without chunk signatures and a checksum trailer, use over https
+    UnsignedTrailer(ContentLength, ChecksumAlgorithm),
+}
+
+impl Transfer {
+    fn payload_hash(&self) -> String {
+        match self {
+            Self::Single => UNSIGNED_PAYLOAD.to_string(),
+            Self::Multiple(_) => STREAM_PAYLOAD.to_string(),
+            Self::MultipleTrailer(..) => STREAM_PAYLOAD_TRAILER.to_string(),
+            Self::UnsignedTrailer(..) => STREAM_UNSIGNED_PAYLOAD_TRAILER.to_string(),
+        }
+    }
+
+    fn checksum(&self) -> Option<ChecksumAlgorithm> {
+        match self {
+            Self::MultipleTrailer(_, algorithm) | Self::UnsignedTrailer(_, algorithm) => {
+                Some(*algorithm)
+            }
+            _ => None,
+        }
+    }
+
+    fn signed_chunks(&self) -> bool {
+        !matches!(self, Self::UnsignedTrailer(..))
+    }
+
+    fn extra_headers(&self) -> MHeader {
+        let mut headers = HashMap::new();
+        match self {
+            Self::Single => (),
+            Self::Multiple(content_len)
+            | Self::MultipleTrailer(content_len, _)
+            | Self::UnsignedTrailer(content_len, _) => {
+                headers.insert("Content-Encoding".to_string(), "aws-chunked".to_string());
+                headers.insert(
+                    "x-amz-decoded-content-length".to_string(),
+                    content_len.clone(),
+                );
+            }
+        }
+        if let Some(algorithm) = self.checksum() {
+            headers.insert(
+                "x-amz-trailer".to_string(),
+                algorithm.header_name().to_string(),
+            );
+        }
+        headers
+    }
+}
+
+pub trait ChunkExt {
+    fn save_remote(&self, url: &str, chunk_kb: usize, bucket: &str, key: &str) -> S3Result<()>;
+}
+
+impl ChunkExt for Client {
+    fn save_remote(&self, url: &str, chunk_kb: usize, bucket: &str, key: &str) -> S3Result<()> {
+        let response1 = ureq::get(url)
+            .call()
+            .map_err(|e| S3Error::other(format! {"Get remote resource failed: {}", e}))?;
+        let content_len = response1
+            .header("Content-Length")
+            .ok_or(S3Error::other("Response has no content-length"))?;
+        let input = PutObjectInput {
+            bucket: bucket.to_string(),
+            key: key.to_string(),
+            content_len: content_len.to_string(),
+            data: response1.into_reader(),
+            options: ObjectOptions::default(),
+        };
+        self.put_object_stream(chunk_kb, input)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn get_object_input() {
+        let date = DateTime::parse_from_rfc2822("Wed, 21 Oct 2015 07:28:00 GMT")
+            .unwrap()
+            .with_timezone(&Utc);
+        let mut input = GetObjectInput::new("bucket", "dir/a b.txt").with_range(100, Some(199));
+        input.if_none_match = Some("\"abc\"".to_string());
+        input.if_modified_since = Some(date);
+        input.response_content_disposition = Some("attachment; filename=\"a b.txt\"".to_string());
+        input.version_id = Some("v1".to_string());
+
+        let headers = input.headers();
+        assert_eq!(headers["Range"], "bytes=100-199");
+        assert_eq!(headers["If-None-Match"], "\"abc\"");
+        assert_eq!(
+            headers["If-Modified-Since"],
+            "Wed, 21 Oct 2015 07:28:00 GMT"
+        );
+        assert!(!headers.contains_key("If-Match"));
+        assert!(!headers.contains_key("x-amz-server-side-encryption-customer-key"));
+
+        input.sse_customer_key = Some(CustomerKey::new([3; 32]));
+        let headers = input.headers();
+        assert_eq!(
+            headers["x-amz-server-side-encryption-customer-algorithm"],
+            "AES256"
+        );
+        assert_eq!(
+            headers["x-amz-server-side-encryption-customer-key-MD5"],
+            util::md5(vec![3; 32])
+        );
+
+        assert_eq!(
+            query_string(&input.query()),
+            "response-content-disposition=attachment%3B%20filename%3D%22a%20b.txt%22&versionId=v1"
+        );
+        assert_eq!(
+            GetObjectInput::new("b", "k").with_range(5, None).range,
+            Some("bytes=5-".to_string())
+        );
+        assert_eq!(
+            GetObjectInput::new("b", "k").with_suffix_range(10).range,
+            Some("bytes=-10".to_string())
+        );
+    }
+
+    #[test]
+    fn object_metadata() {
+        let resp = "HTTP/1.1 206 Partial Content\r\n\
+            ETag: \"3858f62230ac3c915f300c664312c11f\"\r\n\
+            Content-Length: 100\r\n\
+            Content-Type: text/plain\r\n\
+            Content-Range: bytes 100-199/1000\r\n\
+            Last-Modified: Wed, 21 Oct 2015 07:28:00 GMT\r\n\
+            x-amz-version-id: v1\r\n\
+            X-Amz-Meta-Owner: alice\r\n\
+            x-amz-meta-build: 42\r\n\
+            \r\n"
+            .parse::<ureq::Response>()
+            .unwrap();
+        let metadata = ObjectMetadata::from_response(&resp);
+        assert_eq!(
+            metadata.etag.as_deref(),
+            Some("\"3858f62230ac3c915f300c664312c11f\"")
+        );
+        assert_eq!(metadata.content_length, Some(100));
+        assert_eq!(metadata.content_type.as_deref(), Some("text/plain"));
+        assert_eq!(
+            metadata.content_range.as_deref(),
+            Some("bytes 100-199/1000")
+        );
+        assert_eq!(
+            metadata.last_modified.map(|d| d.timestamp()),
+            Some(1_445_412_480)
+        );
+        assert_eq!(metadata.version_id.as_deref(), Some("v1"));
+        assert_eq!(metadata.metadata.len(), 2);
+        assert_eq!(metadata.metadata["owner"], "alice");
+        assert_eq!(metadata.metadata["build"], "42");
+    }
+
+    #[test]
+    fn object_options_headers() {
+        let mut options = ObjectOptions {
+            content_type: Some("text/html".to_string()),
+            cache_control: Some("no-cache".to_string()),
+            content_disposition: Some("attachment; filename=\"a.html\"".to_string()),
+            storage_class: Some(StorageClass::StandardIa),
+            acl: Some(CannedAcl::BucketOwnerFullControl),
+            ..Default::default()
+        };
+        options
+            .metadata
+            .insert("owner".to_string(), "alice".to_string());
+        options
+            .tagging
+            .insert("project".to_string(), "a b".to_string());
+        options
+            .tagging
+            .insert("env".to_string(), "prod".to_string());
+
+        let headers = options.headers();
+        assert_eq!(headers.len(), 7);
+        assert_eq!(headers["Content-Type"], "text/html");
+        assert_eq!(headers["Cache-Control"], "no-cache");
+        assert_eq!(
+            headers["Content-Disposition"],
+            "attachment; filename=\"a.html\""
+        );
+        assert_eq!(headers["x-amz-meta-owner"], "alice");
+        assert_eq!(headers["x-amz-storage-class"], "STANDARD_IA");
+        assert_eq!(headers["x-amz-acl"], "bucket-owner-full-control");
+        assert_eq!(headers["x-amz-tagging"], "env=prod&project=a%20b");
+        assert!(ObjectOptions::default().headers().is_empty());
+        assert!(options.part_headers().is_empty());
+
+        options.encryption = Some(ServerSideEncryption::kms("alias/data"));
+        let headers = options.headers();
+        assert_eq!(headers["x-amz-server-side-encryption"], "aws:kms");
+        assert_eq!(
+            headers["x-amz-server-side-encryption-aws-kms-key-id"],
+            "alias/data"
+        );
+        assert!(options.part_headers().is_empty());
+    }
+
+    #[test]
+    fn encrypted_client_uploads() {
+        let client = Client::new("us-east-1".to_string()).with_encryption(MasterKey::new([5; 32]));
+        let input = PutObjectInput::new("bucket", "key", DEFAULT_MULTIPART_THRESHOLD, &[][..]);
+        assert!(client.upload(input).is_err());
+        let input = PutObjectInput::new("bucket", "key", 0, &[][..]);
+        assert!(client.put_object_stream(64, input).is_err());
+        let input = PutObjectInput::new("bucket", "key", MAX_COPY_SIZE, &[][..]);
+        assert!(client.parallel_uploader().upload(input).is_err());
+    }
+
+    #[test]
+    fn zero_chunk_size() {
+        let client = Client::new("us-east-1".to_string());
+        let input = PutObjectInput::new("bucket", "key", 4, &b"abcd"[..]);
+        let err = client.put_object_stream(0, input).unwrap_err();
+        assert!(err.to_string().contains("chunk size"));
+    }
+
+    #[test]
+    fn head_object_output() {
+        let found = "HTTP/1.1 200 OK\r\nETag: \"abc\"\r\nContent-Length: 4\r\n\r\n"
+            .parse::<ureq::Response>()
+            .unwrap();
+        let output = head_output(Ok(found)).unwrap();
+        assert!(output.exists());
+        assert_eq!(output.metadata().unwrap().content_length, Some(4));
+
+        let missing = "HTTP/1.1 404 Not Found\r\n\r\n"
+            .parse::<ureq::Response>()
+            .unwrap();
+        let output = head_output(Err(ureq::Error::Status(404, missing))).unwrap();
+        assert_eq!(output, HeadObjectOutput::NotFound);
+        assert!(output.metadata().is_none());
+
+        let denied = "HTTP/1.1 403 Forbidden\r\n\r\n"
+            .parse::<ureq::Response>()
+            .unwrap();
+        assert!(head_output(Err(ureq::Error::Status(403, denied))).is_err());
+    }
+
+    #[test]
+    fn delete_objects_xml() {
+        let mut objects = vec![ObjectIdentifier::new("a&b<c>.txt")];
+        objects.push(ObjectIdentifier {
+            key: "d".to_string(),
+            version_id: Some("v1".to_string()),
+        });
+        assert_eq!(
+            delete_objects_body(&objects, true),
+            "<Delete><Quiet>true</Quiet>\
+             <Object><Key>a&amp;b&lt;c&gt;.txt</Key></Object>\
+             <Object><Key>d</Key><VersionId>v1</VersionId></Object></Delete>"
+        );
+        //Content-MD5 of the body
+        assert_eq!(
+            util::md5(delete_objects_body(&objects[1..], false).into_bytes()),
+            "xiSlhNW8hwV2m6h7FdxcCw=="
+        );
+
+        let resp = r#"<?xml version="1.0" encoding="UTF-8"?>
+            <DeleteResult xmlns="http://s3.amazonaws.com/doc/2006-03-01/">
+              <Deleted><Key>a&amp;b</Key></Deleted>
+              <Deleted>
+                <Key>d</Key><DeleteMarker>true</DeleteMarker>
+                <DeleteMarkerVersionId>m1</DeleteMarkerVersionId>
+              </Deleted>
+              <Error><Key>e</Key><Code>AccessDenied</Code><Message>Access Denied</Message></Error>
+            </DeleteResult>"#;
+        let output = DeleteObjectsOutput::from_xml(resp).unwrap();
+        assert_eq!(output.deleted.len(), 2);
+        assert_eq!(output.deleted[0].key, "a&b");
+        assert!(!output.deleted[0].delete_marker);
+        assert!(output.deleted[1].delete_marker);
+        assert_eq!(
+            output.deleted[1].delete_marker_version_id.as_deref(),
+            Some("m1")
+        );
+        assert_eq!(
+            output.errors,
+            vec![DeleteError {
+                key: "e".to_string(),
+                version_id: None,
+                code: "AccessDenied".to_string(),
+                message: "Access Denied".to_string(),
+            }]
+        );
+
+        let error = "<Error><Code>InternalError</Code></Error>";
+        assert!(DeleteObjectsOutput::from_xml(error).is_err());
+    }
+
+    #[test]
+    fn list_objects_xml() {
+        let mut input = ListObjectsInput::new("bucket");
+        input.prefix = Some("photos/".to_string());
+        input.delimiter = Some("/".to_string());
+        input.max_keys = Some(2);
+        input.continuation_token =
+            Some("1ueGcxLPRx1Tr/XYExHnhbYLgveDs2J/wm36Hy4vbOwM=".to_string());
+        assert_eq!(
+            query_string(&input.query()),
+            "list-type=2\
+             &continuation-token=1ueGcxLPRx1Tr%2FXYExHnhbYLgveDs2J%2Fwm36Hy4vbOwM%3D\
+             &delimiter=%2F&max-keys=2&prefix=photos%2F"
+        );
+
+        let resp = r#"<?xml version="1.0" encoding="UTF-8"?>
+            <ListBucketResult xmlns="http://s3.amazonaws.com/doc/2006-03-01/">
+              <Name>bucket</Name>
+              <Prefix>photos/</Prefix>
+              <KeyCount>2</KeyCount>
+              <MaxKeys>2</MaxKeys>
+              <Delimiter>/</Delimiter>
+              <IsTruncated>true</IsTruncated>
+              <NextContinuationToken>next-token</NextContinuationToken>
+              <Contents>
+                <Key>photos/a.jpg</Key>
+                <LastModified>2009-10-12T17:50:30.000Z</LastModified>
+                <ETag>&quot;fba9dede5f27731c9771645a39863328&quot;</ETag>
+                <Size>434234</Size>
+                <StorageClass>STANDARD</StorageClass>
+              </Contents>
+              <CommonPrefixes><Prefix>photos/2006/</Prefix></CommonPrefixes>
+            </ListBucketResult>"#;
+        let output = ListObjectsOutput::from_xml(resp).unwrap();
+        assert!(output.is_truncated);
+        assert_eq!(
+            output.next_continuation_token.as_deref(),
+            Some("next-token")
+        );
+        assert_eq!(output.key_count, 2);
+        assert_eq!(
+            output.objects,
+            vec![Object {
+                key: "photos/a.jpg".to_string(),
+                last_modified: Some(
+                    DateTime::parse_from_rfc3339("2009-10-12T17:50:30Z")
+                        .unwrap()
+                        .with_timezone(&Utc)
+                ),
+                etag: Some("\"fba9dede5f27731c9771645a39863328\"".to_string()),
+                size: 434234,
+                storage_class: Some("STANDARD".to_string()),
+            }]
+        );
+        assert_eq!(
+            output.common_prefixes,
+            vec![CommonPrefix {
+                prefix: "photos/2006/".to_string()
+            }]
+        );
+    }
+}
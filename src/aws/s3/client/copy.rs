@@ -0,0 +1,404 @@
+// https://docs.aws.amazon.com/AmazonS3/latest/API/API_CopyObject.html
+use super::multipart::part_size;
+use super::*;
+
+//largest object a single CopyObject can copy
+pub const MAX_COPY_SIZE: u64 = 5 * 1024 * 1024 * 1024;
+pub const COPY_PART_SIZE: usize = 512 * 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MetadataDirective {
+    //keep the metadata of the source, the default
+    Copy,
+    //use content_type and metadata of the input
+    Replace,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct CopyObjectInput {
+    pub source_bucket: String,
+    pub source_key: String,
+    pub source_version_id: Option<String>,
+    pub bucket: String,
+    pub key: String,
+    pub metadata_directive: Option<MetadataDirective>,
+    pub content_type: Option<String>,
+    //x-amz-meta-* without the prefix
+    pub metadata: HashMap<String, String>,
+    //x-amz-copy-source-if-* conditions on the source
+    pub if_match: Option<String>,
+    pub if_none_match: Option<String>,
+    pub if_modified_since: Option<DateTime<Utc>>,
+    pub if_unmodified_since: Option<DateTime<Utc>>,
+    //encryption of the copy, independent of the source
+    pub encryption: Option<ServerSideEncryption>,
+    //required when the source is stored with SSE-C
+    pub source_sse_customer_key: Option<CustomerKey>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CopyObjectOutput {
+    pub etag: Option<String>,
+    pub last_modified: Option<DateTime<Utc>>,
+    pub version_id: Option<String>,
+    pub source_version_id: Option<String>,
+}
+
+impl CopyObjectInput {
+    pub fn new(source_bucket: &str, source_key: &str, bucket: &str, key: &str) -> Self {
+        Self {
+            source_bucket: source_bucket.to_string(),
+            source_key: source_key.to_string(),
+            bucket: bucket.to_string(),
+            key: key.to_string(),
+            ..Default::default()
+        }
+    }
+
+    //x-amz-copy-source value, the key is URI-encoded keeping slashes
+    fn copy_source(&self) -> String {
+        let mut source = format!(
+            "/{}/{}",
+            self.source_bucket,
+            util::uri_encode(&self.source_key, false)
+        );
+        if let Some(version_id) = &self.source_version_id {
+            source.push_str("?versionId=");
+            source.push_str(&util::uri_encode(version_id, true));
+        }
+        source
+    }
+
+    //copy source and its conditions, shared by CopyObject and UploadPartCopy
+    fn source_headers(&self) -> MHeader {
+        let mut headers = HashMap::new();
+        headers.insert("x-amz-copy-source".to_string(), self.copy_source());
+        let mut set = |name: &str, value: Option<String>| {
+            if let Some(value) = value {
+                headers.insert(name.to_string(), value);
+            }
+        };
+        set("x-amz-copy-source-if-match", self.if_match.clone());
+        set(
+            "x-amz-copy-source-if-none-match",
+            self.if_none_match.clone(),
+        );
+        set(
+            "x-amz-copy-source-if-modified-since",
+            self.if_modified_since
+                .map(|d| d.format(util::GMT_DATETIME).to_string()),
+        );
+        set(
+            "x-amz-copy-source-if-unmodified-since",
+            self.if_unmodified_since
+                .map(|d| d.format(util::GMT_DATETIME).to_string()),
+        );
+        if let Some(key) = &self.source_sse_customer_key {
+            headers.extend(key.headers(COPY_SOURCE_SSE_CUSTOMER));
+        }
+        headers
+    }
+
+    fn headers(&self) -> MHeader {
+        let mut headers = self.source_headers();
+        if let Some(directive) = self.metadata_directive {
+            let value = match directive {
+                MetadataDirective::Copy => "COPY",
+                MetadataDirective::Replace => "REPLACE",
+            };
+            headers.insert("x-amz-metadata-directive".to_string(), value.to_string());
+        }
+        if self.metadata_directive == Some(MetadataDirective::Replace) {
+            headers.extend(object_headers(&self.content_type, &self.metadata));
+        }
+        if let Some(encryption) = &self.encryption {
+            headers.extend(encryption.headers());
+        }
+        headers
+    }
+
+    //replaced metadata keeps the encryption envelope of the source metadata
+    fn with_envelope(&self, source: &HashMap<String, String>) -> Self {
+        let mut input = self.clone();
+        if input.metadata_directive == Some(MetadataDirective::Replace) {
+            input.metadata.extend(envelope(source));
+        }
+        input
+    }
+
+    //source headers and the SSE-C key of the target
+    fn part_headers(&self) -> MHeader {
+        let mut headers = self.source_headers();
+        if let Some(encryption) = &self.encryption {
+            headers.extend(encryption.part_headers());
+        }
+        headers
+    }
+}
+
+fn object_headers(content_type: &Option<String>, metadata: &HashMap<String, String>) -> MHeader {
+    ObjectOptions {
+        content_type: content_type.clone(),
+        metadata: metadata.clone(),
+        ..Default::default()
+    }
+    .headers()
+}
+
+impl Client {
+    //server-side copy of objects up to MAX_COPY_SIZE. With a master key
+    //MetadataDirective::Replace is refused unless the metadata carries the
+    //encryption envelope, copy() takes it from the source
+    pub fn copy_object(&self, input: &CopyObjectInput) -> S3Result<CopyObjectOutput> {
+        if self.encryption.is_some()
+            && input.metadata_directive == Some(MetadataDirective::Replace)
+            && !is_encrypted(&input.metadata)
+        {
+            return Err(S3Error::other(
+                "replacing the metadata drops the encryption envelope, use copy",
+            ));
+        }
+        self.send_copy_object(input)
+    }
+
+    fn send_copy_object(&self, input: &CopyObjectInput) -> S3Result<CopyObjectOutput> {
+        self.retry.run("Copy object", |_| {
+            let resp =
+                self.try_send_bytes("PUT", &input.bucket, &input.key, &[], input.headers(), &[])?;
+            let version_id = resp.header("x-amz-version-id").map(|v| v.to_string());
+            let source_version_id = resp
+                .header("x-amz-copy-source-version-id")
+                .map(|v| v.to_string());
+            let text = resp
+                .into_string()
+                .map_err(|e| S3Error::Transport(format!("read response failed: {}", e)))?;
+            let (etag, last_modified) = copy_result_from_xml(&text, "CopyObjectResult")?;
+            Ok(CopyObjectOutput {
+                etag,
+                last_modified,
+                version_id,
+                source_version_id,
+            })
+        })
+    }
+
+    //copy the inclusive byte range of the source into one part
+    pub fn upload_part_copy(
+        &self,
+        input: &CopyObjectInput,
+        upload_id: &str,
+        part_number: u32,
+        range: (u64, u64),
+    ) -> S3Result<CompletedPart> {
+        let query = vec![
+            ("partNumber".to_string(), part_number.to_string()),
+            ("uploadId".to_string(), upload_id.to_string()),
+        ];
+        let mut extra = input.part_headers();
+        extra.insert(
+            "x-amz-copy-source-range".to_string(),
+            format!("bytes={}-{}", range.0, range.1),
+        );
+        let (etag, _) = self.retry.run("Upload part copy", |_| {
+            let resp =
+                self.try_send_bytes("PUT", &input.bucket, &input.key, &query, extra.clone(), &[])?;
+            let text = resp
+                .into_string()
+                .map_err(|e| S3Error::Transport(format!("read response failed: {}", e)))?;
+            copy_result_from_xml(&text, "CopyPartResult")
+        })?;
+        Ok(CompletedPart {
+            part_number,
+            etag: etag.ok_or_else(|| S3Error::other("copy part response has no ETag"))?,
+        })
+    }
+
+    //CopyObject up to MAX_COPY_SIZE, UploadPartCopy above it. The metadata
+    //directive is honoured for both, the encryption envelope of a client-side
+    //encrypted source is always kept. A multipart copy is aborted on failure
+    pub fn copy(&self, input: &CopyObjectInput) -> S3Result<CopyObjectOutput> {
+        let mut source = GetObjectInput::new(&input.source_bucket, &input.source_key);
+        source.version_id = input.source_version_id.clone();
+        source.sse_customer_key = input.source_sse_customer_key.clone();
+        let metadata = match self.head_object(source)? {
+            HeadObjectOutput::Found(metadata) => metadata,
+            HeadObjectOutput::NotFound => {
+                return Err(S3Error::from(ServiceError {
+                    status: 404,
+                    code: "NoSuchKey".to_string(),
+                    message: "copy source not found".to_string(),
+                    resource: Some(input.copy_source()),
+                    ..Default::default()
+                }))
+            }
+        };
+        let input = &input.with_envelope(&metadata.metadata);
+        let size = metadata.content_length.unwrap_or_default();
+        if size <= MAX_COPY_SIZE {
+            return self.send_copy_object(input);
+        }
+
+        let mut extra = match input.metadata_directive {
+            Some(MetadataDirective::Replace) => {
+                object_headers(&input.content_type, &input.metadata)
+            }
+            _ => object_headers(&metadata.content_type, &metadata.metadata),
+        };
+        if let Some(encryption) = &input.encryption {
+            extra.extend(encryption.headers());
+        }
+        let upload_id = self.send_create_multipart_upload(&input.bucket, &input.key, extra)?;
+        let result = copy_ranges(size, part_size(COPY_PART_SIZE, Some(size)))
+            .into_iter()
+            .enumerate()
+            .map(|(i, range)| self.upload_part_copy(input, &upload_id, i as u32 + 1, range))
+            .collect::<S3Result<Vec<CompletedPart>>>()
+            .and_then(|parts| {
+                self.complete_multipart_upload(&input.bucket, &input.key, &upload_id, &parts)
+            });
+
+        match result {
+            Ok(output) => Ok(CopyObjectOutput {
+                etag: output.etag,
+                last_modified: None,
+                version_id: output.version_id,
+                source_version_id: metadata.version_id,
+            }),
+            Err(e) => {
+                log::info!("Multipart copy {} failed, aborting: {}", upload_id, e);
+                if let Err(e) = self.abort_multipart_upload(&input.bucket, &input.key, &upload_id) {
+                    log::info!("Abort multipart upload {} failed: {}", upload_id, e);
+                }
+                Err(e)
+            }
+        }
+    }
+}
+
+//inclusive byte ranges covering size bytes
+fn copy_ranges(size: u64, part_size: usize) -> Vec<(u64, u64)> {
+    let part_size = part_size as u64;
+    (0..size.div_ceil(part_size))
+        .map(|i| (i * part_size, ((i + 1) * part_size).min(size) - 1))
+        .collect()
+}
+
+//ETag and LastModified of CopyObjectResult or CopyPartResult, S3 may
+//answer 200 and report a failure in the body
+fn copy_result_from_xml(
+    text: &str,
+    root_name: &str,
+) -> S3Result<(Option<String>, Option<DateTime<Utc>>)> {
+    let doc = parse_xml(text)?;
+    let root = doc.root_element();
+    if root.tag_name().name() != root_name {
+        return Err(unexpected_body(text));
+    }
+    let last_modified = child_text(root, "LastModified")
+        .and_then(|v| DateTime::parse_from_rfc3339(&v).ok())
+        .map(|d| d.with_timezone(&Utc));
+    Ok((child_text(root, "ETag"), last_modified))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn copy_object_headers() {
+        let mut input = CopyObjectInput::new("src", "dir/a b+ü.txt", "dst", "copy.txt");
+        input.source_version_id = Some("3/L4kqtJl".to_string());
+        input.if_match = Some("\"abc\"".to_string());
+        assert_eq!(
+            input.copy_source(),
+            "/src/dir/a%20b%2B%C3%BC.txt?versionId=3%2FL4kqtJl"
+        );
+
+        let headers = input.headers();
+        assert_eq!(headers["x-amz-copy-source-if-match"], "\"abc\"");
+        assert!(!headers.contains_key("x-amz-metadata-directive"));
+        assert!(!headers.contains_key("Content-Type"));
+
+        input.metadata_directive = Some(MetadataDirective::Replace);
+        input.content_type = Some("text/plain".to_string());
+        input
+            .metadata
+            .insert("owner".to_string(), "alice".to_string());
+        let headers = input.headers();
+        assert_eq!(headers["x-amz-metadata-directive"], "REPLACE");
+        assert_eq!(headers["Content-Type"], "text/plain");
+        assert_eq!(headers["x-amz-meta-owner"], "alice");
+
+        input.encryption = Some(ServerSideEncryption::Customer(CustomerKey::new([1; 32])));
+        input.source_sse_customer_key = Some(CustomerKey::new([2; 32]));
+        let headers = input.headers();
+        assert_eq!(
+            headers["x-amz-server-side-encryption-customer-key-MD5"],
+            util::md5(vec![1; 32])
+        );
+        assert_eq!(
+            headers["x-amz-copy-source-server-side-encryption-customer-key-MD5"],
+            util::md5(vec![2; 32])
+        );
+        let headers = input.part_headers();
+        assert!(headers.contains_key("x-amz-server-side-encryption-customer-key"));
+        assert!(headers.contains_key("x-amz-copy-source-server-side-encryption-customer-key"));
+        assert!(!headers.contains_key("Content-Type"));
+    }
+
+    #[test]
+    fn copy_object_envelope() {
+        let mut source = HashMap::new();
+        source.insert("x-amz-key-v2".to_string(), "d3JhcHBlZA==".to_string());
+        source.insert("x-amz-iv".to_string(), "aXY=".to_string());
+        source.insert("x-amz-cek-alg".to_string(), "AES/GCM/NoPadding".to_string());
+        source.insert("owner".to_string(), "alice".to_string());
+
+        let mut input = CopyObjectInput::new("src", "a", "dst", "b");
+        assert!(input.with_envelope(&source).metadata.is_empty());
+
+        input.metadata_directive = Some(MetadataDirective::Replace);
+        input
+            .metadata
+            .insert("owner".to_string(), "bob".to_string());
+        let headers = input.with_envelope(&source).headers();
+        assert_eq!(headers["x-amz-meta-x-amz-key-v2"], "d3JhcHBlZA==");
+        assert_eq!(headers["x-amz-meta-x-amz-iv"], "aXY=");
+        assert_eq!(headers["x-amz-meta-x-amz-cek-alg"], "AES/GCM/NoPadding");
+        assert_eq!(headers["x-amz-meta-owner"], "bob");
+
+        //refused before any request is sent
+        let client = Client::new("us-east-1".to_string()).with_encryption(MasterKey::new([5; 32]));
+        let err = client.copy_object(&input).unwrap_err();
+        assert!(err.to_string().contains("envelope"));
+    }
+
+    #[test]
+    fn copy_object_ranges() {
+        assert_eq!(copy_ranges(10, 4), vec![(0, 3), (4, 7), (8, 9)]);
+        assert_eq!(copy_ranges(8, 4), vec![(0, 3), (4, 7)]);
+        let size = 6 * 1024 * 1024 * 1024;
+        let ranges = copy_ranges(size, part_size(COPY_PART_SIZE, Some(size)));
+        assert_eq!(ranges.len(), 12);
+        assert_eq!(ranges[11].1, size - 1);
+    }
+
+    #[test]
+    fn copy_object_xml() {
+        let resp = r#"<?xml version="1.0" encoding="UTF-8"?>
+            <CopyObjectResult>
+              <LastModified>2009-10-28T22:32:00.000Z</LastModified>
+              <ETag>"9b2cf535f27731c974343645a3985328"</ETag>
+            </CopyObjectResult>"#;
+        let (etag, last_modified) = copy_result_from_xml(resp, "CopyObjectResult").unwrap();
+        assert_eq!(
+            etag.as_deref(),
+            Some("\"9b2cf535f27731c974343645a3985328\"")
+        );
+        assert_eq!(last_modified.map(|d| d.timestamp()), Some(1_256_769_120));
+
+        assert!(copy_result_from_xml(resp, "CopyPartResult").is_err());
+        let failed = "<Error><Code>InternalError</Code></Error>";
+        assert!(copy_result_from_xml(failed, "CopyObjectResult").is_err());
+    }
+}
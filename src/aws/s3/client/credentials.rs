@@ -0,0 +1,64 @@
+//where the client takes its keys from, asked again for every request so
+//providers can refresh temporary credentials
+use super::*;
+use std::fmt;
+
+#[derive(Clone, PartialEq, Eq)]
+pub struct Credentials {
+    pub access_key: String,
+    pub secret_key: String,
+    //set for temporary credentials, sent as X-Amz-Security-Token
+    pub session_token: Option<String>,
+}
+
+pub trait CredentialsProvider: Send + Sync {
+    fn credentials(&self) -> S3Result<Credentials>;
+}
+
+//AWS_ACCESS_KEY_ID, AWS_SECRET_ACCESS_KEY and AWS_SESSION_TOKEN, the default
+#[derive(Debug, Clone, Copy, Default)]
+pub struct EnvCredentials;
+
+impl Credentials {
+    pub fn new(access_key: &str, secret_key: &str) -> Self {
+        Self {
+            access_key: access_key.to_string(),
+            secret_key: secret_key.to_string(),
+            session_token: None,
+        }
+    }
+
+    pub fn with_session_token(mut self, token: &str) -> Self {
+        self.session_token = Some(token.to_string());
+        self
+    }
+}
+
+//fixed credentials
+impl CredentialsProvider for Credentials {
+    fn credentials(&self) -> S3Result<Credentials> {
+        Ok(self.clone())
+    }
+}
+
+impl CredentialsProvider for EnvCredentials {
+    fn credentials(&self) -> S3Result<Credentials> {
+        let var = |name: &str| {
+            std::env::var(name).map_err(|_| S3Error::other(format!("{} not set", name)))
+        };
+        Ok(Credentials {
+            access_key: var("AWS_ACCESS_KEY_ID")?,
+            secret_key: var("AWS_SECRET_ACCESS_KEY")?,
+            session_token: std::env::var("AWS_SESSION_TOKEN").ok(),
+        })
+    }
+}
+
+//the secret never shows up in logs
+impl fmt::Debug for Credentials {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Credentials")
+            .field("access_key", &self.access_key)
+            .finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,225 @@
+//client-side envelope encryption, every object gets its own AES-256-GCM data
+//key which is stored wrapped by the master key in the object metadata. The
+//metadata names follow the v2 format of the AWS S3 encryption clients
+use super::*;
+use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM, NONCE_LEN};
+use ring::rand::{SecureRandom, SystemRandom};
+use std::convert::TryInto;
+use std::fmt;
+
+const CONTENT_ALGORITHM: &str = "AES/GCM/NoPadding";
+const WRAP_ALGORITHM: &str = "AES/GCM";
+//x-amz-meta-* names without the prefix
+const META_KEY: &str = "x-amz-key-v2";
+const META_IV: &str = "x-amz-iv";
+const META_CEK_ALG: &str = "x-amz-cek-alg";
+const META_WRAP_ALG: &str = "x-amz-wrap-alg";
+const META_TAG_LEN: &str = "x-amz-tag-len";
+const META_MATDESC: &str = "x-amz-matdesc";
+const META_PLAIN_LEN: &str = "x-amz-unencrypted-content-length";
+const ENVELOPE: [&str; 7] = [
+    META_KEY,
+    META_IV,
+    META_CEK_ALG,
+    META_WRAP_ALG,
+    META_TAG_LEN,
+    META_MATDESC,
+    META_PLAIN_LEN,
+];
+
+//256-bit key wrapping the data keys, it never leaves the host
+#[derive(Clone, PartialEq, Eq)]
+pub struct MasterKey([u8; 32]);
+
+impl MasterKey {
+    pub fn new(key: [u8; 32]) -> Self {
+        Self(key)
+    }
+
+    pub fn from_slice(key: &[u8]) -> S3Result<Self> {
+        let key = key
+            .try_into()
+            .map_err(|_| S3Error::other("master key must be 32 bytes"))?;
+        Ok(Self(key))
+    }
+
+    //ciphertext with the tag appended and the x-amz-meta-* envelope headers
+    pub(super) fn encrypt(&self, mut data: Vec<u8>) -> S3Result<(Vec<u8>, MHeader)> {
+        let rng = SystemRandom::new();
+        let mut data_key = [0u8; 32];
+        let mut iv = [0u8; NONCE_LEN];
+        let mut wrap_iv = [0u8; NONCE_LEN];
+        rng.fill(&mut data_key)
+            .and_then(|_| rng.fill(&mut iv))
+            .and_then(|_| rng.fill(&mut wrap_iv))
+            .map_err(|_| S3Error::other("generate data key failed"))?;
+        let plain_len = data.len();
+
+        seal(&data_key, iv, Aad::empty(), &mut data)?;
+        let mut wrapped = data_key.to_vec();
+        seal(&self.0, wrap_iv, Aad::from(CONTENT_ALGORITHM), &mut wrapped)?;
+        let envelope = [
+            (META_KEY, base64::encode([&wrap_iv[..], &wrapped].concat())),
+            (META_IV, base64::encode(iv)),
+            (META_CEK_ALG, CONTENT_ALGORITHM.to_string()),
+            (META_WRAP_ALG, WRAP_ALGORITHM.to_string()),
+            (META_TAG_LEN, "128".to_string()),
+            (META_MATDESC, "{}".to_string()),
+            (META_PLAIN_LEN, plain_len.to_string()),
+        ];
+        let headers = envelope
+            .iter()
+            .map(|(k, v)| (format!("x-amz-meta-{}", k), v.clone()))
+            .collect();
+        Ok((data, headers))
+    }
+
+    //metadata is ObjectMetadata::metadata of the encrypted object
+    pub(super) fn decrypt(
+        &self,
+        metadata: &HashMap<String, String>,
+        mut data: Vec<u8>,
+    ) -> S3Result<Vec<u8>> {
+        let invalid = |name: &str| S3Error::other(format!("invalid encryption metadata {}", name));
+        let get = |name: &str| metadata.get(name).ok_or_else(|| invalid(name));
+        if get(META_CEK_ALG)? != CONTENT_ALGORITHM || get(META_WRAP_ALG)? != WRAP_ALGORITHM {
+            return Err(S3Error::other(format!(
+                "unsupported encryption {} wrapped with {}",
+                get(META_CEK_ALG)?,
+                get(META_WRAP_ALG)?
+            )));
+        }
+        let wrapped = base64::decode(get(META_KEY)?).map_err(|_| invalid(META_KEY))?;
+        let iv = base64::decode(get(META_IV)?).map_err(|_| invalid(META_IV))?;
+        if wrapped.len() < NONCE_LEN {
+            return Err(invalid(META_KEY));
+        }
+
+        let (wrap_iv, wrapped) = wrapped.split_at(NONCE_LEN);
+        let mut data_key = wrapped.to_vec();
+        let data_key = open(
+            &self.0,
+            wrap_iv,
+            Aad::from(CONTENT_ALGORITHM),
+            &mut data_key,
+        )
+        .map_err(|_| S3Error::other("unwrap data key failed, wrong master key?"))?;
+        let len = open(data_key, &iv, Aad::empty(), &mut data)
+            .map_err(|_| S3Error::other("decrypt object failed"))?
+            .len();
+        data.truncate(len);
+        Ok(data)
+    }
+}
+
+//the key never shows up in logs
+impl fmt::Debug for MasterKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("MasterKey(..)")
+    }
+}
+
+//objects written by the encryption client carry the wrapped data key
+pub(super) fn is_encrypted(metadata: &HashMap<String, String>) -> bool {
+    metadata.contains_key(META_KEY)
+}
+
+//envelope entries of the metadata, a copy replacing the metadata has to
+//keep them or the object can not be decrypted anymore
+pub(super) fn envelope(metadata: &HashMap<String, String>) -> HashMap<String, String> {
+    metadata
+        .iter()
+        .filter(|(k, _)| ENVELOPE.contains(&k.as_str()))
+        .map(|(k, v)| (k.clone(), v.clone()))
+        .collect()
+}
+
+fn seal<A: AsRef<[u8]>>(
+    key: &[u8],
+    iv: [u8; NONCE_LEN],
+    aad: Aad<A>,
+    data: &mut Vec<u8>,
+) -> S3Result<()> {
+    let key = UnboundKey::new(&AES_256_GCM, key).map_err(|_| S3Error::other("invalid key"))?;
+    LessSafeKey::new(key)
+        .seal_in_place_append_tag(Nonce::assume_unique_for_key(iv), aad, data)
+        .map_err(|_| S3Error::other("encrypt failed"))
+}
+
+//the plaintext part of data
+fn open<'a, A: AsRef<[u8]>>(
+    key: &[u8],
+    iv: &[u8],
+    aad: Aad<A>,
+    data: &'a mut [u8],
+) -> S3Result<&'a [u8]> {
+    let failed = |_| S3Error::other("decrypt failed");
+    let key = UnboundKey::new(&AES_256_GCM, key).map_err(failed)?;
+    let nonce = Nonce::try_assume_unique_for_key(iv).map_err(failed)?;
+    LessSafeKey::new(key)
+        .open_in_place(nonce, aad, data)
+        .map(|plain| &*plain)
+        .map_err(failed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //envelope headers as returned in ObjectMetadata::metadata
+    fn metadata(headers: &MHeader) -> HashMap<String, String> {
+        headers
+            .iter()
+            .map(|(k, v)| {
+                (
+                    k.strip_prefix("x-amz-meta-").unwrap().to_string(),
+                    v.clone(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn encryption_round_trip() {
+        let key = MasterKey::new([9; 32]);
+        let plain = b"hello envelope".to_vec();
+        let (data, headers) = key.encrypt(plain.clone()).unwrap();
+        assert_eq!(data.len(), plain.len() + 16);
+        assert_ne!(&data[..plain.len()], &plain[..]);
+        assert_eq!(headers["x-amz-meta-x-amz-cek-alg"], "AES/GCM/NoPadding");
+        assert_eq!(headers["x-amz-meta-x-amz-unencrypted-content-length"], "14");
+
+        let metadata = metadata(&headers);
+        assert!(is_encrypted(&metadata));
+        assert_eq!(key.decrypt(&metadata, data.clone()).unwrap(), plain);
+
+        //a second object gets another data key
+        let (other, _) = key.encrypt(plain.clone()).unwrap();
+        assert_ne!(other, data);
+
+        assert!(MasterKey::new([8; 32])
+            .decrypt(&metadata, data.clone())
+            .is_err());
+        let mut corrupt = data.clone();
+        corrupt[0] ^= 1;
+        assert!(key.decrypt(&metadata, corrupt).is_err());
+        let mut unsupported = metadata.clone();
+        unsupported.insert(META_CEK_ALG.to_string(), "AES/CBC/PKCS5Padding".to_string());
+        assert!(key.decrypt(&unsupported, data).is_err());
+        assert!(!is_encrypted(&HashMap::new()));
+
+        let mut with_user = metadata.clone();
+        with_user.insert("owner".to_string(), "alice".to_string());
+        assert_eq!(envelope(&with_user), metadata);
+    }
+
+    #[test]
+    fn encryption_empty_object() {
+        let key = MasterKey::from_slice(&[1; 32]).unwrap();
+        let (data, headers) = key.encrypt(Vec::new()).unwrap();
+        assert_eq!(data.len(), 16);
+        assert!(key.decrypt(&metadata(&headers), data).unwrap().is_empty());
+        assert!(MasterKey::from_slice(&[1; 31]).is_err());
+        assert_eq!(format!("{:?}", key), "MasterKey(..)");
+    }
+}
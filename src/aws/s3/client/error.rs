@@ -0,0 +1,232 @@
+// https://docs.aws.amazon.com/AmazonS3/latest/API/ErrorResponses.html
+use super::{child_text, parse_xml};
+use simple_error::SimpleError;
+use std::fmt;
+
+pub type S3Result<T> = std::result::Result<T, S3Error>;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum S3Error {
+    //error response of the service
+    Service(Box<ServiceError>),
+    //connection, DNS or TLS failure, nothing was received
+    Transport(String),
+    //local failure, e.g. reading the input or an invalid response
+    Other(String),
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ServiceError {
+    pub status: u16,
+    pub code: String,
+    pub message: String,
+    pub request_id: Option<String>,
+    pub host_id: Option<String>,
+    pub resource: Option<String>,
+    //set on SignatureDoesNotMatch, compare with Sign::string_to_sign
+    pub string_to_sign: Option<String>,
+    pub canonical_request: Option<String>,
+}
+
+impl S3Error {
+    pub fn other<S: Into<String>>(message: S) -> Self {
+        Self::Other(message.into())
+    }
+
+    pub fn service(&self) -> Option<&ServiceError> {
+        match self {
+            Self::Service(e) => Some(e.as_ref()),
+            _ => None,
+        }
+    }
+
+    pub fn is_not_found(&self) -> bool {
+        self.service().is_some_and(|e| {
+            e.status == 404
+                || matches!(
+                    e.code.as_str(),
+                    "NoSuchKey" | "NoSuchBucket" | "NoSuchUpload" | "NoSuchVersion" | "NotFound"
+                )
+        })
+    }
+
+    pub fn is_access_denied(&self) -> bool {
+        self.service()
+            .is_some_and(|e| e.code == "AccessDenied" || (e.status == 403 && e.code.is_empty()))
+    }
+
+    //throttling, server errors and dropped connections
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Self::Service(e) => {
+                matches!(e.status, 429 | 500 | 502 | 503 | 504)
+                    || matches!(
+                        e.code.as_str(),
+                        "InternalError"
+                            | "SlowDown"
+                            | "ServiceUnavailable"
+                            | "RequestTimeout"
+                            | "RequestTimeTooSkewed"
+                    )
+            }
+            Self::Transport(_) => true,
+            Self::Other(_) => false,
+        }
+    }
+}
+
+impl ServiceError {
+    //error xml of the body, HEAD responses and some proxies have none
+    pub fn from_response(resp: ureq::Response) -> Self {
+        let status = resp.status();
+        let request_id = resp.header("x-amz-request-id").map(|v| v.to_string());
+        let host_id = resp.header("x-amz-id-2").map(|v| v.to_string());
+        let body = resp.into_string().unwrap_or_default();
+        let mut error = Self::from_xml(status, &body).unwrap_or_else(|| Self {
+            status,
+            message: body,
+            ..Default::default()
+        });
+        error.request_id = error.request_id.or(request_id);
+        error.host_id = error.host_id.or(host_id);
+        error
+    }
+
+    pub fn from_xml(status: u16, body: &str) -> Option<Self> {
+        let doc = parse_xml(body).ok()?;
+        let root = doc.root_element();
+        if root.tag_name().name() != "Error" {
+            return None;
+        }
+        Some(Self {
+            status,
+            code: child_text(root, "Code").unwrap_or_default(),
+            message: child_text(root, "Message").unwrap_or_default(),
+            request_id: child_text(root, "RequestId"),
+            host_id: child_text(root, "HostId"),
+            resource: child_text(root, "Resource"),
+            string_to_sign: child_text(root, "StringToSign"),
+            canonical_request: child_text(root, "CanonicalRequest"),
+        })
+    }
+}
+
+//a 200 response whose body is not the expected result, S3 reports some
+//failures of CompleteMultipartUpload and CopyObject this way
+pub(super) fn unexpected_body(body: &str) -> S3Error {
+    match ServiceError::from_xml(200, body) {
+        Some(error) => S3Error::from(error),
+        None => S3Error::other(format!("unexpected response: {}", body)),
+    }
+}
+
+impl fmt::Display for S3Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Service(e) => write!(
+                f,
+                "Http failed {}, {}: {} (request id {})",
+                e.status,
+                e.code,
+                e.message,
+                e.request_id.as_deref().unwrap_or("-")
+            ),
+            Self::Transport(e) => write!(f, "Http failed {}", e),
+            Self::Other(e) => f.write_str(e),
+        }
+    }
+}
+
+impl std::error::Error for S3Error {}
+
+impl From<ServiceError> for S3Error {
+    fn from(e: ServiceError) -> Self {
+        Self::Service(Box::new(e))
+    }
+}
+
+impl From<SimpleError> for S3Error {
+    fn from(e: SimpleError) -> Self {
+        Self::Other(e.as_str().to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn service_error_xml() {
+        let resp = "HTTP/1.1 404 Not Found\r\nx-amz-id-2: host-id\r\n\r\n\
+            <?xml version=\"1.0\" encoding=\"UTF-8\"?>\
+            <Error>\
+              <Code>NoSuchKey</Code>\
+              <Message>The resource you requested does not exist</Message>\
+              <Resource>/mybucket/myfoto.jpg</Resource>\
+              <RequestId>4442587FB7D0A2F9</RequestId>\
+            </Error>"
+            .parse::<ureq::Response>()
+            .unwrap();
+        let error = S3Error::from(ServiceError::from_response(resp));
+        assert!(error.is_not_found());
+        assert!(!error.is_access_denied());
+        assert!(!error.is_retryable());
+        let service = error.service().unwrap();
+        assert_eq!(service.resource.as_deref(), Some("/mybucket/myfoto.jpg"));
+        assert_eq!(service.request_id.as_deref(), Some("4442587FB7D0A2F9"));
+        assert_eq!(service.host_id.as_deref(), Some("host-id"));
+        assert_eq!(
+            error.to_string(),
+            "Http failed 404, NoSuchKey: The resource you requested does not exist \
+             (request id 4442587FB7D0A2F9)"
+        );
+
+        let body = "<Error><Code>SignatureDoesNotMatch</Code>\
+            <StringToSign>AWS4-HMAC-SHA256\n20130524T000000Z</StringToSign>\
+            <CanonicalRequest>GET\n/\n</CanonicalRequest></Error>";
+        let error = ServiceError::from_xml(403, body).unwrap();
+        assert_eq!(
+            error.string_to_sign.as_deref(),
+            Some("AWS4-HMAC-SHA256\n20130524T000000Z")
+        );
+        assert_eq!(error.canonical_request.as_deref(), Some("GET\n/\n"));
+        assert!(!S3Error::from(error).is_access_denied());
+
+        let resp = "HTTP/1.1 403 Forbidden\r\nx-amz-request-id: 0A49CE4060975EAC\r\n\r\n"
+            .parse::<ureq::Response>()
+            .unwrap();
+        let error = S3Error::from(ServiceError::from_response(resp));
+        assert!(error.is_access_denied());
+        assert_eq!(
+            error.service().unwrap().request_id.as_deref(),
+            Some("0A49CE4060975EAC")
+        );
+    }
+
+    #[test]
+    fn service_error_classes() {
+        let error = |status: u16, code: &str| {
+            S3Error::from(ServiceError {
+                status,
+                code: code.to_string(),
+                ..Default::default()
+            })
+        };
+        assert!(error(503, "SlowDown").is_retryable());
+        assert!(error(500, "InternalError").is_retryable());
+        assert!(error(200, "InternalError").is_retryable());
+        assert!(error(400, "RequestTimeout").is_retryable());
+        assert!(!error(400, "InvalidArgument").is_retryable());
+        assert!(error(403, "AccessDenied").is_access_denied());
+        //HEAD responses carry no body
+        assert!(error(403, "").is_access_denied());
+        assert!(error(404, "").is_not_found());
+        assert!(error(404, "NoSuchUpload").is_not_found());
+        assert!(S3Error::Transport("connection reset".to_string()).is_retryable());
+        assert!(!S3Error::other("input data read err").is_retryable());
+
+        let failed = unexpected_body("<Error><Code>InternalError</Code></Error>");
+        assert!(failed.is_retryable());
+        assert!(matches!(unexpected_body("garbage"), S3Error::Other(_)));
+    }
+}
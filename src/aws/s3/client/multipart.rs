@@ -0,0 +1,348 @@
+// https://docs.aws.amazon.com/AmazonS3/latest/userguide/mpuoverview.html
+use super::*;
+
+//every part but the last must have at least this size
+pub const MIN_PART_SIZE: usize = 5 * 1024 * 1024;
+pub const MAX_PARTS: u64 = 10_000;
+pub const DEFAULT_PART_SIZE: usize = 8 * 1024 * 1024;
+pub const DEFAULT_MULTIPART_THRESHOLD: u64 = 8 * 1024 * 1024;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompletedPart {
+    pub part_number: u32,
+    pub etag: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CompleteMultipartUploadOutput {
+    pub location: Option<String>,
+    pub etag: Option<String>,
+    pub version_id: Option<String>,
+}
+
+impl Client {
+    //returns the upload id
+    pub fn create_multipart_upload(&self, bucket: &str, key: &str) -> S3Result<String> {
+        self.create_multipart_upload_with(bucket, key, &ObjectOptions::default())
+    }
+
+    //options are stored with the object once the upload completes
+    pub fn create_multipart_upload_with(
+        &self,
+        bucket: &str,
+        key: &str,
+        options: &ObjectOptions,
+    ) -> S3Result<String> {
+        self.send_create_multipart_upload(bucket, key, options.headers())
+    }
+
+    //extra holds the object headers, e.g. Content-Type and x-amz-meta-*
+    pub(super) fn send_create_multipart_upload(
+        &self,
+        bucket: &str,
+        key: &str,
+        extra: MHeader,
+    ) -> S3Result<String> {
+        let query = vec![("uploads".to_string(), "".to_string())];
+        let resp = self.send_bytes("POST", bucket, key, &query, extra, &[])?;
+        let text = resp
+            .into_string()
+            .map_err(|e| S3Error::other(format!("read response failed: {}", e)))?;
+        upload_id_from_xml(&text)
+    }
+
+    //part_number starts at 1
+    pub fn upload_part(
+        &self,
+        bucket: &str,
+        key: &str,
+        upload_id: &str,
+        part_number: u32,
+        data: &[u8],
+    ) -> S3Result<CompletedPart> {
+        let options = ObjectOptions::default();
+        self.upload_part_with(bucket, key, upload_id, part_number, data, &options)
+    }
+
+    //options must be the ones the upload was created with, only an SSE-C
+    //key is sent again with the part
+    pub fn upload_part_with(
+        &self,
+        bucket: &str,
+        key: &str,
+        upload_id: &str,
+        part_number: u32,
+        data: &[u8],
+        options: &ObjectOptions,
+    ) -> S3Result<CompletedPart> {
+        self.retry.run("Upload part", |_| {
+            self.try_upload_part(bucket, key, upload_id, part_number, data, options)
+        })
+    }
+
+    //single attempt of upload_part_with
+    pub(super) fn try_upload_part(
+        &self,
+        bucket: &str,
+        key: &str,
+        upload_id: &str,
+        part_number: u32,
+        data: &[u8],
+        options: &ObjectOptions,
+    ) -> S3Result<CompletedPart> {
+        let query = vec![
+            ("partNumber".to_string(), part_number.to_string()),
+            ("uploadId".to_string(), upload_id.to_string()),
+        ];
+        let extra = options.part_headers();
+        let resp = self.try_send_bytes("PUT", bucket, key, &query, extra, data)?;
+        let etag = resp
+            .header("ETag")
+            .ok_or_else(|| S3Error::other("upload part response has no ETag"))?;
+        Ok(CompletedPart {
+            part_number,
+            etag: etag.to_string(),
+        })
+    }
+
+    //parts must be in ascending part number order
+    pub fn complete_multipart_upload(
+        &self,
+        bucket: &str,
+        key: &str,
+        upload_id: &str,
+        parts: &[CompletedPart],
+    ) -> S3Result<CompleteMultipartUploadOutput> {
+        let query = vec![("uploadId".to_string(), upload_id.to_string())];
+        let mut extra = HashMap::new();
+        extra.insert("Content-Type".to_string(), "application/xml".to_string());
+        let body = complete_body(parts);
+        //a failure reported in a 200 body is retried as well
+        self.retry.run("Complete multipart upload", |_| {
+            let resp =
+                self.try_send_bytes("POST", bucket, key, &query, extra.clone(), body.as_bytes())?;
+            let version_id = resp.header("x-amz-version-id").map(|v| v.to_string());
+            let text = resp
+                .into_string()
+                .map_err(|e| S3Error::Transport(format!("read response failed: {}", e)))?;
+            let mut output = CompleteMultipartUploadOutput::from_xml(&text)?;
+            output.version_id = version_id;
+            Ok(output)
+        })
+    }
+
+    pub fn abort_multipart_upload(&self, bucket: &str, key: &str, upload_id: &str) -> S3Result<()> {
+        let query = vec![("uploadId".to_string(), upload_id.to_string())];
+        self.send_bytes("DELETE", bucket, key, &query, HashMap::new(), &[])?;
+        Ok(())
+    }
+
+    //single put_object below the multipart threshold, multipart above it or
+    //when content_len is not a number, the upload is aborted on failure.
+    //For multipart, progress and rate limit apply to reading the source
+    pub fn upload<T: Read>(&self, input: PutObjectInput<T>) -> S3Result<()> {
+        let size = input.content_len.parse::<u64>().ok();
+        if let Some(size) = size {
+            if size < self.multipart_threshold {
+                return self.put_object(input);
+            }
+        }
+        self.check_unencrypted()?;
+
+        let part_size = part_size(self.part_size, size);
+        let PutObjectInput {
+            bucket,
+            key,
+            data,
+            options,
+            ..
+        } = input;
+        let mut reader = Monitor::new(data, size)
+            .with_progress(self.progress.clone())
+            .with_rate_limit(self.limiter.clone());
+
+        let mut buf = Vec::with_capacity(part_size);
+        if read_part(&mut reader, &mut buf, part_size)? < part_size {
+            //the whole body fits in one part
+            self.send_bytes("PUT", &bucket, &key, &[], options.headers(), &buf)?;
+            return Ok(());
+        }
+
+        let upload_id = self.create_multipart_upload_with(&bucket, &key, &options)?;
+        let upload_part = |part_number: u32, data: &[u8]| {
+            self.upload_part_with(&bucket, &key, &upload_id, part_number, data, &options)
+        };
+        let result = upload_parts(&mut reader, buf, part_size, upload_part)
+            .and_then(|parts| self.complete_multipart_upload(&bucket, &key, &upload_id, &parts));
+        if let Err(e) = &result {
+            log::info!("Multipart upload {} failed, aborting: {}", upload_id, e);
+            if let Err(e) = self.abort_multipart_upload(&bucket, &key, &upload_id) {
+                log::info!("Abort multipart upload {} failed: {}", upload_id, e);
+            }
+        }
+        result.map(|_| ())
+    }
+}
+
+//buf holds the first part
+fn upload_parts<R, F>(
+    reader: &mut R,
+    mut buf: Vec<u8>,
+    part_size: usize,
+    upload: F,
+) -> S3Result<Vec<CompletedPart>>
+where
+    R: Read,
+    F: Fn(u32, &[u8]) -> S3Result<CompletedPart>,
+{
+    let mut parts = Vec::new();
+    loop {
+        let part_number = parts.len() as u32 + 1;
+        if part_number as u64 > MAX_PARTS {
+            return Err(S3Error::other("multipart upload exceeds 10000 parts"));
+        }
+        parts.push(upload(part_number, &buf)?);
+        if read_part(reader, &mut buf, part_size)? == 0 {
+            return Ok(parts);
+        }
+    }
+}
+
+//smallest part size not below the configured one that fits size in MAX_PARTS
+pub(super) fn part_size(configured: usize, size: Option<u64>) -> usize {
+    let configured = configured.max(MIN_PART_SIZE);
+    match size {
+        Some(size) => configured.max(size.div_ceil(MAX_PARTS) as usize),
+        None => configured,
+    }
+}
+
+//fill buf with up to size bytes, fewer only at the end of the reader
+pub(super) fn read_part<R: Read>(
+    reader: &mut R,
+    buf: &mut Vec<u8>,
+    size: usize,
+) -> S3Result<usize> {
+    buf.clear();
+    reader
+        .by_ref()
+        .take(size as u64)
+        .read_to_end(buf)
+        .map_err(|e| S3Error::other(format!("input data read err: {}", e)))
+}
+
+fn complete_body(parts: &[CompletedPart]) -> String {
+    let mut body = String::from("<CompleteMultipartUpload>");
+    for part in parts {
+        body.push_str(&format!(
+            "<Part><PartNumber>{}</PartNumber><ETag>{}</ETag></Part>",
+            part.part_number,
+            xml_escape(&part.etag)
+        ));
+    }
+    body.push_str("</CompleteMultipartUpload>");
+    body
+}
+
+fn upload_id_from_xml(text: &str) -> S3Result<String> {
+    let doc = parse_xml(text)?;
+    let root = doc.root_element();
+    match root.tag_name().name() {
+        "InitiateMultipartUploadResult" => child_text(root, "UploadId")
+            .ok_or_else(|| S3Error::other("create multipart upload response has no UploadId")),
+        _ => Err(unexpected_body(text)),
+    }
+}
+
+impl CompleteMultipartUploadOutput {
+    //S3 may answer 200 and report a failure in the body
+    fn from_xml(text: &str) -> S3Result<Self> {
+        let doc = parse_xml(text)?;
+        let root = doc.root_element();
+        if root.tag_name().name() != "CompleteMultipartUploadResult" {
+            return Err(unexpected_body(text));
+        }
+        Ok(Self {
+            location: child_text(root, "Location"),
+            etag: child_text(root, "ETag"),
+            version_id: None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn multipart_part_size() {
+        assert_eq!(part_size(1024, None), MIN_PART_SIZE);
+        assert_eq!(part_size(DEFAULT_PART_SIZE, Some(100)), DEFAULT_PART_SIZE);
+        //1 TB needs parts larger than 100 MB
+        let size = 1_000_000_000_000u64;
+        let fitted = part_size(DEFAULT_PART_SIZE, Some(size));
+        assert_eq!(fitted, 100_000_000);
+        assert!(size.div_ceil(fitted as u64) <= MAX_PARTS);
+    }
+
+    #[test]
+    fn multipart_read_part() {
+        let data = [7u8; 25];
+        let mut reader = &data[..];
+        let mut buf = Vec::new();
+        let sizes = (0..4)
+            .map(|_| read_part(&mut reader, &mut buf, 10).unwrap())
+            .collect::<Vec<usize>>();
+        assert_eq!(sizes, vec![10, 10, 5, 0]);
+    }
+
+    #[test]
+    fn multipart_xml() {
+        let parts = vec![
+            CompletedPart {
+                part_number: 1,
+                etag: "\"a54357aff0632cce46d942af68356b38\"".to_string(),
+            },
+            CompletedPart {
+                part_number: 2,
+                etag: "\"0c78aef83f66abc1fa1e8477f296d394\"".to_string(),
+            },
+        ];
+        assert_eq!(
+            complete_body(&parts),
+            "<CompleteMultipartUpload>\
+             <Part><PartNumber>1</PartNumber><ETag>&quot;a54357aff0632cce46d942af68356b38&quot;</ETag></Part>\
+             <Part><PartNumber>2</PartNumber><ETag>&quot;0c78aef83f66abc1fa1e8477f296d394&quot;</ETag></Part>\
+             </CompleteMultipartUpload>"
+        );
+
+        let initiate = r#"<?xml version="1.0" encoding="UTF-8"?>
+            <InitiateMultipartUploadResult xmlns="http://s3.amazonaws.com/doc/2006-03-01/">
+              <Bucket>example-bucket</Bucket>
+              <Key>example-object</Key>
+              <UploadId>VXBsb2FkIElEIGZvciA2aWWpbmcncyBteS1tb3ZpZS5tMnRzIHVwbG9hZA</UploadId>
+            </InitiateMultipartUploadResult>"#;
+        assert_eq!(
+            upload_id_from_xml(initiate).unwrap(),
+            "VXBsb2FkIElEIGZvciA2aWWpbmcncyBteS1tb3ZpZS5tMnRzIHVwbG9hZA"
+        );
+
+        let complete = r#"<?xml version="1.0" encoding="UTF-8"?>
+            <CompleteMultipartUploadResult xmlns="http://s3.amazonaws.com/doc/2006-03-01/">
+              <Location>http://Example-Bucket.s3.amazonaws.com/Example-Object</Location>
+              <Bucket>Example-Bucket</Bucket>
+              <Key>Example-Object</Key>
+              <ETag>"3858f62230ac3c915f300c664312c11f-9"</ETag>
+            </CompleteMultipartUploadResult>"#;
+        let output = CompleteMultipartUploadOutput::from_xml(complete).unwrap();
+        assert_eq!(
+            output.etag.as_deref(),
+            Some("\"3858f62230ac3c915f300c664312c11f-9\"")
+        );
+
+        let failed = r#"<?xml version="1.0" encoding="UTF-8"?>
+            <Error><Code>InternalError</Code><Message>We encountered an internal error.</Message></Error>"#;
+        assert!(CompleteMultipartUploadOutput::from_xml(failed).is_err());
+        assert!(upload_id_from_xml(failed).is_err());
+    }
+}
@@ -0,0 +1,325 @@
+//multipart upload with parts sent concurrently from worker threads
+use super::multipart::{part_size, read_part};
+use super::*;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{sync_channel, Receiver};
+use std::sync::{Arc, Mutex};
+
+pub const DEFAULT_CONCURRENCY: usize = 4;
+
+//the source is read on the calling thread, at most max_memory bytes of
+//part buffers exist at any time and at most concurrency parts are in flight.
+//Failed parts are retried by the client's RetryPolicy or with_part_retries
+pub struct ParallelUploader<'a> {
+    client: &'a Client,
+    concurrency: usize,
+    max_memory: usize,
+    part_retries: Option<u32>,
+}
+
+impl Client {
+    pub fn parallel_uploader(&self) -> ParallelUploader<'_> {
+        ParallelUploader {
+            client: self,
+            concurrency: DEFAULT_CONCURRENCY,
+            max_memory: DEFAULT_CONCURRENCY * self.part_size,
+            part_retries: None,
+        }
+    }
+}
+
+impl<'a> ParallelUploader<'a> {
+    pub fn with_concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    //at least one part buffer is always allocated
+    pub fn with_max_memory(mut self, bytes: usize) -> Self {
+        self.max_memory = bytes;
+        self
+    }
+
+    //extra attempts for a failed part before the upload is aborted, in
+    //place of the max_attempts of the client's RetryPolicy
+    pub fn with_part_retries(mut self, retries: u32) -> Self {
+        self.part_retries = Some(retries);
+        self
+    }
+
+    fn part_retry(&self) -> RetryPolicy {
+        let mut retry = self.client.retry.clone();
+        if let Some(retries) = self.part_retries {
+            retry.max_attempts = retries.saturating_add(1);
+        }
+        retry
+    }
+
+    //same contract as Client::upload
+    pub fn upload<T: Read>(&self, input: PutObjectInput<T>) -> S3Result<()> {
+        let client = self.client;
+        let size = input.content_len.parse::<u64>().ok();
+        if let Some(size) = size {
+            if size < client.multipart_threshold {
+                return client.put_object(input);
+            }
+        }
+        client.check_unencrypted()?;
+
+        let part_size = part_size(client.part_size, size);
+        let PutObjectInput {
+            bucket,
+            key,
+            data,
+            options,
+            ..
+        } = input;
+        let mut reader = Monitor::new(data, size)
+            .with_progress(client.progress.clone())
+            .with_rate_limit(client.limiter.clone());
+
+        let mut first = Vec::with_capacity(part_size);
+        if read_part(&mut reader, &mut first, part_size)? < part_size {
+            client.send_bytes("PUT", &bucket, &key, &[], options.headers(), &first)?;
+            return Ok(());
+        }
+
+        let upload_id = client.create_multipart_upload_with(&bucket, &key, &options)?;
+        let slots = (self.max_memory / part_size).max(1);
+        let retry = self.part_retry();
+        let upload_part = |part_number: u32, data: &[u8]| {
+            retry.run("Upload part", |_| {
+                client.try_upload_part(&bucket, &key, &upload_id, part_number, data, &options)
+            })
+        };
+        let result = Parts {
+            part_size,
+            slots,
+            workers: self.concurrency.min(slots),
+        }
+        .run(&mut reader, first, upload_part)
+        .and_then(|parts| client.complete_multipart_upload(&bucket, &key, &upload_id, &parts));
+
+        if let Err(e) = &result {
+            log::info!("Multipart upload {} failed, aborting: {}", upload_id, e);
+            if let Err(e) = client.abort_multipart_upload(&bucket, &key, &upload_id) {
+                log::info!("Abort multipart upload {} failed: {}", upload_id, e);
+            }
+        }
+        result.map(|_| ())
+    }
+}
+
+struct Parts {
+    part_size: usize,
+    //number of part buffers
+    slots: usize,
+    workers: usize,
+}
+
+impl Parts {
+    //first holds part 1, returns the parts sorted by part number
+    fn run<R, F>(&self, reader: &mut R, first: Vec<u8>, upload: F) -> S3Result<Vec<CompletedPart>>
+    where
+        R: Read,
+        F: Fn(u32, &[u8]) -> S3Result<CompletedPart> + Sync,
+    {
+        let (free_tx, free_rx) = sync_channel::<Vec<u8>>(self.slots);
+        let (work_tx, work_rx) = sync_channel::<(u32, Vec<u8>)>(self.slots);
+        let (done_tx, done_rx) = std::sync::mpsc::channel();
+        let work_rx = Arc::new(Mutex::new(work_rx));
+        let failed = AtomicBool::new(false);
+        for _ in 1..self.slots {
+            free_tx
+                .send(Vec::new())
+                .map_err(|_| S3Error::other("buffer pool closed"))?;
+        }
+        //only the workers hold the pool sender and the queue receiver, the
+        //channels close once every worker has exited, even by a panic
+        let handles = (0..self.workers)
+            .map(|_| (free_tx.clone(), done_tx.clone(), work_rx.clone()))
+            .collect::<Vec<_>>();
+        drop((free_tx, done_tx, work_rx));
+        let gone = || S3Error::other("upload workers exited");
+
+        let read_result = std::thread::scope(|scope| {
+            for (free_tx, done_tx, work_rx) in handles {
+                let (failed, upload) = (&failed, &upload);
+                scope.spawn(move || {
+                    let _guard = PanicGuard(failed);
+                    while let Some((part_number, buf)) = next_work(&work_rx) {
+                        //after a failure the remaining parts are only drained
+                        if !failed.load(Ordering::SeqCst) {
+                            let result = upload(part_number, &buf);
+                            if result.is_err() {
+                                failed.store(true, Ordering::SeqCst);
+                            }
+                            let _ = done_tx.send(result);
+                        }
+                        let _ = free_tx.send(buf);
+                    }
+                });
+            }
+
+            let mut buf = first;
+            let mut part_number = 1u32;
+            let result = loop {
+                if work_tx.send((part_number, buf)).is_err() {
+                    break Err(gone());
+                }
+                buf = match free_rx.recv() {
+                    Ok(buf) => buf,
+                    Err(_) => break Err(gone()),
+                };
+                if failed.load(Ordering::SeqCst) {
+                    break Ok(());
+                }
+                match read_part(reader, &mut buf, self.part_size) {
+                    Ok(0) => break Ok(()),
+                    Ok(_) if part_number as u64 >= MAX_PARTS => {
+                        break Err(S3Error::other("multipart upload exceeds 10000 parts"))
+                    }
+                    Ok(_) => part_number += 1,
+                    Err(e) => break Err(e),
+                }
+            };
+            if result.is_err() {
+                failed.store(true, Ordering::SeqCst);
+            }
+            //workers exit once the queue is empty and closed
+            drop(work_tx);
+            result
+        });
+
+        let mut parts = Vec::new();
+        for result in done_rx {
+            parts.push(result?);
+        }
+        read_result?;
+        parts.sort_by_key(|p| p.part_number);
+        Ok(parts)
+    }
+}
+
+//a poisoned queue ends the worker like a closed one
+fn next_work<T>(work_rx: &Mutex<Receiver<T>>) -> Option<T> {
+    work_rx.lock().ok()?.recv().ok()
+}
+
+//stops the reader when a worker panics
+struct PanicGuard<'a>(&'a AtomicBool);
+
+impl Drop for PanicGuard<'_> {
+    fn drop(&mut self) {
+        if std::thread::panicking() {
+            self.0.store(true, Ordering::SeqCst);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+    use std::time::Duration;
+
+    fn parts(slots: usize, workers: usize) -> Parts {
+        Parts {
+            part_size: 10,
+            slots,
+            workers,
+        }
+    }
+
+    #[test]
+    fn parallel_parts_in_order() {
+        let data = (0..95u8).collect::<Vec<u8>>();
+        let mut reader = &data[10..];
+        let uploaded = Mutex::new(Vec::new());
+        let in_flight = AtomicUsize::new(0);
+        let max_in_flight = AtomicUsize::new(0);
+        let upload = |part_number: u32, buf: &[u8]| {
+            let now = in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+            max_in_flight.fetch_max(now, Ordering::SeqCst);
+            std::thread::sleep(Duration::from_millis(5));
+            uploaded.lock().unwrap().push((part_number, buf.to_vec()));
+            in_flight.fetch_sub(1, Ordering::SeqCst);
+            Ok(CompletedPart {
+                part_number,
+                etag: format!("\"{}\"", part_number),
+            })
+        };
+
+        let result = parts(3, 2)
+            .run(&mut reader, data[..10].to_vec(), upload)
+            .unwrap();
+        let numbers = result.iter().map(|p| p.part_number).collect::<Vec<u32>>();
+        assert_eq!(numbers, (1..=10).collect::<Vec<u32>>());
+        assert!(max_in_flight.load(Ordering::SeqCst) <= 2);
+
+        let mut uploaded = uploaded.into_inner().unwrap();
+        uploaded.sort();
+        let joined = uploaded
+            .into_iter()
+            .flat_map(|(_, b)| b)
+            .collect::<Vec<u8>>();
+        assert_eq!(joined, data);
+    }
+
+    #[test]
+    fn parallel_parts_failure() {
+        let data = [1u8; 30];
+        let mut reader = &data[10..];
+        let attempts = Mutex::new(HashMap::new());
+        let upload = |part_number: u32, _: &[u8]| {
+            *attempts.lock().unwrap().entry(part_number).or_insert(0) += 1;
+            if part_number == 2 {
+                return Err(S3Error::from(ServiceError {
+                    status: 503,
+                    code: "SlowDown".to_string(),
+                    ..Default::default()
+                }));
+            }
+            Ok(CompletedPart {
+                part_number,
+                etag: String::new(),
+            })
+        };
+
+        //retries happen inside the upload closure, a failed part is final here
+        let result = parts(2, 2).run(&mut reader, data[..10].to_vec(), upload);
+        assert!(result.is_err());
+        assert_eq!(attempts.lock().unwrap()[&2], 1);
+    }
+
+    #[test]
+    fn parallel_part_retries() {
+        let client = Client::new("us-east-1".to_string()).with_retry(RetryPolicy::new(5));
+        assert_eq!(client.parallel_uploader().part_retry().max_attempts, 5);
+        let retry = client.parallel_uploader().with_part_retries(0).part_retry();
+        assert_eq!(retry.max_attempts, 1);
+        assert_eq!(retry.max_delay, client.retry.max_delay);
+    }
+
+    #[test]
+    fn parallel_parts_panic() {
+        let data = [1u8; 200];
+        for (slots, workers) in [(1, 1), (2, 2), (4, 2)] {
+            let mut reader = &data[10..];
+            let upload = |part_number: u32, _: &[u8]| {
+                if part_number == 2 {
+                    panic!("part {} panicked", part_number);
+                }
+                Ok(CompletedPart {
+                    part_number,
+                    etag: String::new(),
+                })
+            };
+            //the panic reaches the caller instead of blocking the reader
+            let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                parts(slots, workers).run(&mut reader, data[..10].to_vec(), upload)
+            }));
+            assert!(result.is_err());
+        }
+    }
+}
@@ -0,0 +1,492 @@
+//multipart upload that survives restarts, progress is kept in a local state file
+use super::multipart::{part_size, read_part};
+use super::*;
+use std::io::{Seek, SeekFrom};
+use std::path::Path;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Part {
+    pub part_number: u32,
+    pub etag: String,
+    pub size: u64,
+}
+
+//one line per record, every line is form-urlencoded
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UploadState {
+    pub bucket: String,
+    pub key: String,
+    pub upload_id: String,
+    //source length, a different length starts a new upload
+    pub size: u64,
+    pub part_size: usize,
+    pub parts: Vec<PartState>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PartState {
+    pub part_number: u32,
+    pub etag: String,
+    //base64 Content-MD5 sent with the part
+    pub checksum: String,
+}
+
+impl Client {
+    //all parts of the upload, following NextPartNumberMarker
+    pub fn list_parts(&self, bucket: &str, key: &str, upload_id: &str) -> S3Result<Vec<Part>> {
+        let mut parts = Vec::new();
+        let mut marker: Option<String> = None;
+        loop {
+            let mut query = vec![("uploadId".to_string(), upload_id.to_string())];
+            if let Some(marker) = &marker {
+                query.push(("part-number-marker".to_string(), marker.to_string()));
+            }
+            let text = self.get_string(bucket, key, &query)?;
+            let (page, next) = parts_from_xml(&text)?;
+            parts.extend(page);
+            match next {
+                Some(next) => marker = Some(next),
+                None => return Ok(parts),
+            }
+        }
+    }
+
+    //multipart upload that can be continued after an interruption: the
+    //state file records every finished part, on restart ListParts confirms
+    //them by ETag and size and only the missing parts are read and sent, the
+    //source is trusted to be unchanged. The length is taken from the source,
+    //content_len is ignored. The options apply when the upload is created,
+    //on resume their SSE-C key must be the same. The state file is removed
+    //once the upload completes, failed uploads are not aborted
+    pub fn upload_resumable<T: Read + Seek>(
+        &self,
+        input: PutObjectInput<T>,
+        state_path: &Path,
+    ) -> S3Result<()> {
+        self.resumable_upload(input, state_path, false)
+    }
+
+    //upload_resumable that also reads the recorded parts again on restart,
+    //the ones whose MD5 changed in the source are sent again
+    pub fn upload_resumable_verified<T: Read + Seek>(
+        &self,
+        input: PutObjectInput<T>,
+        state_path: &Path,
+    ) -> S3Result<()> {
+        self.resumable_upload(input, state_path, true)
+    }
+
+    fn resumable_upload<T: Read + Seek>(
+        &self,
+        input: PutObjectInput<T>,
+        state_path: &Path,
+        verify: bool,
+    ) -> S3Result<()> {
+        let PutObjectInput {
+            bucket,
+            key,
+            mut data,
+            options,
+            ..
+        } = input;
+        let (bucket, key) = (bucket.as_str(), key.as_str());
+        let size = data.seek(SeekFrom::End(0)).map_err(seek_err)?;
+        data.seek(SeekFrom::Start(0)).map_err(seek_err)?;
+        if size < self.multipart_threshold {
+            let input = PutObjectInput::new(bucket, key, size, data).with_options(options);
+            return self.put_object(input);
+        }
+        self.check_unencrypted()?;
+
+        let mut state = match self.resume_state(bucket, key, size, state_path)? {
+            Some(state) => state,
+            None => {
+                let state = UploadState {
+                    bucket: bucket.to_string(),
+                    key: key.to_string(),
+                    upload_id: self.create_multipart_upload_with(bucket, key, &options)?,
+                    size,
+                    part_size: part_size(self.part_size, Some(size)),
+                    parts: Vec::new(),
+                };
+                state.save(state_path)?;
+                state
+            }
+        };
+        if verify {
+            verify_parts(&mut state, &mut data)?;
+        }
+
+        //recorded parts count as sent
+        let sent = state
+            .parts
+            .iter()
+            .map(|p| state.part_len(p.part_number))
+            .sum();
+        let mut reader = Monitor::new(data, Some(size))
+            .with_sent(sent)
+            .with_progress(self.progress.clone())
+            .with_rate_limit(self.limiter.clone());
+        let upload_id = state.upload_id.clone();
+        let upload_part = |part_number: u32, data: &[u8]| {
+            self.upload_part_with(bucket, key, &upload_id, part_number, data, &options)
+        };
+        upload_missing(&mut state, state_path, &mut reader, upload_part)?;
+
+        let parts = state
+            .parts
+            .iter()
+            .map(|p| CompletedPart {
+                part_number: p.part_number,
+                etag: p.etag.clone(),
+            })
+            .collect::<Vec<CompletedPart>>();
+        self.complete_multipart_upload(bucket, key, &state.upload_id, &parts)?;
+        std::fs::remove_file(state_path)
+            .map_err(|e| S3Error::other(format!("remove state file failed: {}", e)))?;
+        Ok(())
+    }
+
+    //state of an earlier attempt, keeping only the parts S3 still has with
+    //the recorded ETag and the expected size
+    fn resume_state(
+        &self,
+        bucket: &str,
+        key: &str,
+        size: u64,
+        state_path: &Path,
+    ) -> S3Result<Option<UploadState>> {
+        let mut state = match UploadState::load(state_path)? {
+            Some(state) if state.bucket == bucket && state.key == key && state.size == size => {
+                state
+            }
+            Some(state) => {
+                log::info!("State file is for another upload {}", state.upload_id);
+                return Ok(None);
+            }
+            None => return Ok(None),
+        };
+
+        let remote = match self.list_parts(bucket, key, &state.upload_id) {
+            Ok(parts) => parts,
+            Err(e) if e.is_not_found() => {
+                log::info!("Upload {} no longer exists: {}", state.upload_id, e);
+                return Ok(None);
+            }
+            Err(e) => return Err(e),
+        };
+        let parts = std::mem::take(&mut state.parts);
+        state.parts = parts
+            .into_iter()
+            .filter(|p| {
+                remote.iter().any(|r| {
+                    r.part_number == p.part_number
+                        && r.etag == p.etag
+                        && r.size == state.part_len(p.part_number)
+                })
+            })
+            .collect();
+        log::debug!(
+            "Resuming upload {} with {} parts done",
+            state.upload_id,
+            state.parts.len()
+        );
+        Ok(Some(state))
+    }
+}
+
+//sends the parts the state does not record, recorded parts are not read
+fn upload_missing<R, F>(
+    state: &mut UploadState,
+    state_path: &Path,
+    reader: &mut R,
+    upload: F,
+) -> S3Result<()>
+where
+    R: Read + Seek,
+    F: Fn(u32, &[u8]) -> S3Result<CompletedPart>,
+{
+    let count = state.size.div_ceil(state.part_size as u64).max(1) as u32;
+    let mut buf = Vec::with_capacity(state.part_size);
+    for part_number in 1..=count {
+        if state.parts.iter().any(|p| p.part_number == part_number) {
+            continue;
+        }
+        reader
+            .seek(SeekFrom::Start(state.part_offset(part_number)))
+            .map_err(seek_err)?;
+        read_part(reader, &mut buf, state.part_size)?;
+        let part = upload(part_number, &buf)?;
+        state.parts.push(PartState {
+            part_number,
+            etag: part.etag,
+            checksum: util::md5(&buf),
+        });
+        state.save(state_path)?;
+    }
+    state.parts.sort_by_key(|p| p.part_number);
+    Ok(())
+}
+
+//drops the recorded parts whose bytes changed in the source
+fn verify_parts<R: Read + Seek>(state: &mut UploadState, reader: &mut R) -> S3Result<()> {
+    let mut buf = Vec::with_capacity(state.part_size);
+    for part in std::mem::take(&mut state.parts) {
+        reader
+            .seek(SeekFrom::Start(state.part_offset(part.part_number)))
+            .map_err(seek_err)?;
+        read_part(reader, &mut buf, state.part_size)?;
+        if util::md5(&buf) == part.checksum {
+            state.parts.push(part);
+        } else {
+            log::info!("Part {} changed since it was uploaded", part.part_number);
+        }
+    }
+    Ok(())
+}
+
+fn seek_err(e: std::io::Error) -> S3Error {
+    S3Error::other(format!("input data seek err: {}", e))
+}
+
+//parts of one ListParts page and the marker of the next page
+fn parts_from_xml(text: &str) -> S3Result<(Vec<Part>, Option<String>)> {
+    let doc = parse_xml(text)?;
+    let root = doc.root_element();
+    if root.tag_name().name() != "ListPartsResult" {
+        return Err(unexpected_body(text));
+    }
+
+    let parts = root
+        .children()
+        .filter(|n| n.is_element() && n.tag_name().name() == "Part")
+        .map(|node| Part {
+            part_number: child_text(node, "PartNumber")
+                .and_then(|v| v.parse().ok())
+                .unwrap_or_default(),
+            etag: child_text(node, "ETag").unwrap_or_default(),
+            size: child_text(node, "Size")
+                .and_then(|v| v.parse().ok())
+                .unwrap_or_default(),
+        })
+        .collect();
+    let next = match child_text(root, "IsTruncated").as_deref() {
+        Some("true") => child_text(root, "NextPartNumberMarker"),
+        _ => None,
+    };
+    Ok((parts, next))
+}
+
+impl UploadState {
+    fn part_offset(&self, part_number: u32) -> u64 {
+        (part_number as u64 - 1) * self.part_size as u64
+    }
+
+    //only the last part is shorter
+    fn part_len(&self, part_number: u32) -> u64 {
+        let offset = self.part_offset(part_number).min(self.size);
+        (self.size - offset).min(self.part_size as u64)
+    }
+
+    pub fn load(path: &Path) -> S3Result<Option<Self>> {
+        match std::fs::read_to_string(path) {
+            Ok(text) => Self::parse(&text).map(Some),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(S3Error::other(format!("read state file failed: {}", e))),
+        }
+    }
+
+    //written to a temporary file first so a crash never leaves a torn state
+    pub fn save(&self, path: &Path) -> S3Result<()> {
+        let mut tmp = path.as_os_str().to_owned();
+        tmp.push(".tmp");
+        std::fs::write(&tmp, self.to_string())
+            .and_then(|_| std::fs::rename(&tmp, path))
+            .map_err(|e| S3Error::other(format!("write state file failed: {}", e)))
+    }
+
+    fn parse(text: &str) -> S3Result<Self> {
+        let invalid = || S3Error::other("invalid state file");
+        let mut lines = text.lines().map(|line| {
+            url::form_urlencoded::parse(line.as_bytes())
+                .into_owned()
+                .collect::<HashMap<String, String>>()
+        });
+
+        let mut head = lines.next().ok_or_else(invalid)?;
+        let mut take = |name: &str| head.remove(name).ok_or_else(invalid);
+        let mut state = Self {
+            bucket: take("bucket")?,
+            key: take("key")?,
+            upload_id: take("upload_id")?,
+            size: take("size")?.parse().map_err(|_| invalid())?,
+            part_size: take("part_size")?.parse().map_err(|_| invalid())?,
+            parts: Vec::new(),
+        };
+        for mut line in lines {
+            let mut take = |name: &str| line.remove(name).ok_or_else(invalid);
+            state.parts.push(PartState {
+                part_number: take("part_number")?.parse().map_err(|_| invalid())?,
+                etag: take("etag")?,
+                checksum: take("checksum")?,
+            });
+        }
+        Ok(state)
+    }
+}
+
+impl std::fmt::Display for UploadState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let head = [
+            ("bucket".to_string(), self.bucket.clone()),
+            ("key".to_string(), self.key.clone()),
+            ("upload_id".to_string(), self.upload_id.clone()),
+            ("size".to_string(), self.size.to_string()),
+            ("part_size".to_string(), self.part_size.to_string()),
+        ];
+        writeln!(f, "{}", query_string(&head))?;
+        for part in &self.parts {
+            let line = [
+                ("part_number".to_string(), part.part_number.to_string()),
+                ("etag".to_string(), part.etag.clone()),
+                ("checksum".to_string(), part.checksum.clone()),
+            ];
+            writeln!(f, "{}", query_string(&line))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resumable_state_file() {
+        let state = UploadState {
+            bucket: "bucket".to_string(),
+            key: "dir/a b+c&d=e.bin".to_string(),
+            upload_id: "VXBsb2FkIElE+/=".to_string(),
+            size: 12_000_000,
+            part_size: MIN_PART_SIZE,
+            parts: vec![PartState {
+                part_number: 2,
+                etag: "\"0c78aef83f66abc1fa1e8477f296d394\"".to_string(),
+                checksum: "DHiu+D9mq8H6HoR38paDlA==".to_string(),
+            }],
+        };
+        let path = std::env::temp_dir().join(format!("sloppy_auth_state_{}", std::process::id()));
+        assert_eq!(UploadState::load(&path).unwrap(), None);
+        state.save(&path).unwrap();
+        assert_eq!(UploadState::load(&path).unwrap(), Some(state));
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(UploadState::parse("bucket=b&key=k").is_err());
+        assert!(UploadState::parse("").is_err());
+    }
+
+    fn recorded(part_number: u32, data: &[u8]) -> PartState {
+        PartState {
+            part_number,
+            etag: format!("\"{}\"", part_number),
+            checksum: util::md5(data),
+        }
+    }
+
+    #[test]
+    fn resumable_missing_parts() {
+        let data = (0..45u8).collect::<Vec<u8>>();
+        let mut state = UploadState {
+            bucket: "bucket".to_string(),
+            key: "key".to_string(),
+            upload_id: "id".to_string(),
+            size: 45,
+            part_size: 10,
+            parts: vec![recorded(3, &data[20..30]), recorded(1, &data[..10])],
+        };
+        assert_eq!(state.part_len(1), 10);
+        assert_eq!(state.part_len(5), 5);
+
+        let path = std::env::temp_dir().join(format!("sloppy_auth_missing_{}", std::process::id()));
+        let mut reader = Monitor::new(std::io::Cursor::new(&data), Some(45)).with_sent(20);
+        let uploaded = std::cell::RefCell::new(Vec::new());
+        let upload = |part_number: u32, buf: &[u8]| {
+            uploaded.borrow_mut().push((part_number, buf.to_vec()));
+            Ok(CompletedPart {
+                part_number,
+                etag: format!("\"{}\"", part_number),
+            })
+        };
+        upload_missing(&mut state, &path, &mut reader, upload).unwrap();
+        assert_eq!(UploadState::load(&path).unwrap().unwrap().parts.len(), 5);
+        std::fs::remove_file(&path).unwrap();
+
+        //recorded parts are neither read nor sent
+        assert_eq!(reader.sent(), 45);
+        assert_eq!(
+            uploaded.into_inner(),
+            vec![
+                (2, data[10..20].to_vec()),
+                (4, data[30..40].to_vec()),
+                (5, data[40..].to_vec())
+            ]
+        );
+        let numbers = state
+            .parts
+            .iter()
+            .map(|p| p.part_number)
+            .collect::<Vec<u32>>();
+        assert_eq!(numbers, vec![1, 2, 3, 4, 5]);
+        assert_eq!(state.parts[3], recorded(4, &data[30..40]));
+
+        let mut changed = data.clone();
+        changed[25] = 0;
+        verify_parts(&mut state, &mut std::io::Cursor::new(&changed)).unwrap();
+        let numbers = state
+            .parts
+            .iter()
+            .map(|p| p.part_number)
+            .collect::<Vec<u32>>();
+        assert_eq!(numbers, vec![1, 2, 4, 5]);
+    }
+
+    #[test]
+    fn list_parts_xml() {
+        let resp = r#"<?xml version="1.0" encoding="UTF-8"?>
+            <ListPartsResult xmlns="http://s3.amazonaws.com/doc/2006-03-01/">
+              <Bucket>example-bucket</Bucket>
+              <Key>example-object</Key>
+              <UploadId>XXBsb2FkIElEIGZvciBlbHZpbmcncyVcdS1tb3ZpZS5tMnRzEEEwbG9hZA</UploadId>
+              <PartNumberMarker>1</PartNumberMarker>
+              <NextPartNumberMarker>3</NextPartNumberMarker>
+              <MaxParts>2</MaxParts>
+              <IsTruncated>true</IsTruncated>
+              <Part>
+                <PartNumber>2</PartNumber>
+                <LastModified>2010-11-10T20:48:34.000Z</LastModified>
+                <ETag>"7778aef83f66abc1fa1e8477f296d394"</ETag>
+                <Size>10485760</Size>
+              </Part>
+              <Part>
+                <PartNumber>3</PartNumber>
+                <LastModified>2010-11-10T20:48:33.000Z</LastModified>
+                <ETag>"aaaa18db4cc2f85cedef654fccc4a4x8"</ETag>
+                <Size>10485760</Size>
+              </Part>
+            </ListPartsResult>"#;
+        let (parts, next) = parts_from_xml(resp).unwrap();
+        assert_eq!(next.as_deref(), Some("3"));
+        assert_eq!(
+            parts[0],
+            Part {
+                part_number: 2,
+                etag: "\"7778aef83f66abc1fa1e8477f296d394\"".to_string(),
+                size: 10485760,
+            }
+        );
+        assert_eq!(parts[1].part_number, 3);
+
+        let last = resp.replace("<IsTruncated>true", "<IsTruncated>false");
+        assert_eq!(parts_from_xml(&last).unwrap().1, None);
+    }
+}
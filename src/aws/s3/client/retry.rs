@@ -0,0 +1,155 @@
+//retries of failed requests, every attempt is signed again with a fresh date
+use super::*;
+use ring::rand::{SecureRandom, SystemRandom};
+use std::time::Duration;
+
+pub const DEFAULT_MAX_ATTEMPTS: u32 = 3;
+
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    //attempts including the first one, 1 disables retries
+    pub max_attempts: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+    //errors worth another attempt, S3Error::is_retryable by default
+    pub retryable: fn(&S3Error) -> bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: DEFAULT_MAX_ATTEMPTS,
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_secs(20),
+            retryable: S3Error::is_retryable,
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn new(max_attempts: u32) -> Self {
+        Self {
+            max_attempts: max_attempts.max(1),
+            ..Default::default()
+        }
+    }
+
+    pub fn none() -> Self {
+        Self::new(1)
+    }
+
+    pub fn with_backoff(mut self, base_delay: Duration, max_delay: Duration) -> Self {
+        self.base_delay = base_delay;
+        self.max_delay = max_delay;
+        self
+    }
+
+    pub fn with_retryable(mut self, retryable: fn(&S3Error) -> bool) -> Self {
+        self.retryable = retryable;
+        self
+    }
+
+    //full jitter: uniform in [0, min(max_delay, base_delay * 2^attempt)]
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let cap = self
+            .base_delay
+            .saturating_mul(1 << attempt.min(20))
+            .min(self.max_delay);
+        let mut random = [0u8; 8];
+        if SystemRandom::new().fill(&mut random).is_err() {
+            return cap;
+        }
+        cap.mul_f64(u64::from_le_bytes(random) as f64 / u64::MAX as f64)
+    }
+
+    //calls request with the attempt number until it succeeds, fails with an
+    //error the policy does not retry or runs out of attempts
+    pub(super) fn run<T, F>(&self, name: &str, mut request: F) -> S3Result<T>
+    where
+        F: FnMut(u32) -> S3Result<T>,
+    {
+        let mut attempt = 0;
+        loop {
+            match request(attempt) {
+                Err(e) if (self.retryable)(&e) && attempt + 1 < self.max_attempts => {
+                    let delay = self.delay(attempt);
+                    log::info!("{} failed, retrying in {:?}: {}", name, delay, e);
+                    std::thread::sleep(delay);
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn slow_down() -> S3Error {
+        S3Error::from(ServiceError {
+            status: 503,
+            code: "SlowDown".to_string(),
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn retry_policy_delay() {
+        let policy = RetryPolicy::new(5)
+            .with_backoff(Duration::from_millis(100), Duration::from_millis(500));
+        for attempt in 0..10 {
+            let cap = Duration::from_millis((100 << attempt).min(500));
+            assert!(policy.delay(attempt) <= cap);
+        }
+        assert_eq!(RetryPolicy::new(0).max_attempts, 1);
+    }
+
+    #[test]
+    fn retry_policy_run() {
+        let policy = RetryPolicy::new(3).with_backoff(Duration::ZERO, Duration::ZERO);
+        let mut attempts = Vec::new();
+        let result = policy.run("test", |attempt| {
+            attempts.push(attempt);
+            match attempt {
+                0 => Err(slow_down()),
+                1 => Err(S3Error::Transport("connection reset".to_string())),
+                _ => Ok(attempt),
+            }
+        });
+        assert_eq!(result, Ok(2));
+        assert_eq!(attempts, vec![0, 1, 2]);
+
+        //out of attempts
+        let mut count = 0;
+        let result = policy.run("test", |_| -> S3Result<()> {
+            count += 1;
+            Err(slow_down())
+        });
+        assert!(result.unwrap_err().is_retryable());
+        assert_eq!(count, 3);
+
+        //not retryable
+        let mut count = 0;
+        let result = policy.run("test", |_| -> S3Result<()> {
+            count += 1;
+            Err(S3Error::other("invalid content length"))
+        });
+        assert!(result.is_err());
+        assert_eq!(count, 1);
+
+        //custom classifier
+        let policy = policy.with_retryable(|e| !e.is_retryable());
+        let mut count = 0;
+        let result = policy.run("test", |_| -> S3Result<()> {
+            count += 1;
+            match count {
+                1 => Err(S3Error::other("invalid content length")),
+                _ => Err(slow_down()),
+            }
+        });
+        assert!(result.unwrap_err().is_retryable());
+        assert_eq!(count, 2);
+    }
+}
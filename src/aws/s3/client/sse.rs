@@ -0,0 +1,160 @@
+// https://docs.aws.amazon.com/AmazonS3/latest/userguide/serv-side-encryption.html
+use super::*;
+use std::convert::TryInto;
+use std::fmt;
+
+const SSE: &str = "x-amz-server-side-encryption";
+pub(super) const SSE_CUSTOMER: &str = "x-amz-server-side-encryption-customer";
+pub(super) const COPY_SOURCE_SSE_CUSTOMER: &str =
+    "x-amz-copy-source-server-side-encryption-customer";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ServerSideEncryption {
+    //SSE-S3, keys managed by S3
+    Aes256,
+    //SSE-KMS, the AWS managed key when key_id is None. The context is
+    //additional authenticated data, it must be given again to decrypt
+    //through KMS but not to read the object from S3
+    Kms {
+        key_id: Option<String>,
+        context: HashMap<String, String>,
+    },
+    //SSE-C, the key is sent with every request and never stored by S3
+    Customer(CustomerKey),
+}
+
+//256-bit key for SSE-C
+#[derive(Clone, PartialEq, Eq)]
+pub struct CustomerKey([u8; 32]);
+
+impl ServerSideEncryption {
+    pub fn kms(key_id: &str) -> Self {
+        Self::Kms {
+            key_id: Some(key_id.to_string()),
+            context: HashMap::new(),
+        }
+    }
+
+    //headers of PutObject, CreateMultipartUpload and the CopyObject target
+    pub(super) fn headers(&self) -> MHeader {
+        let mut headers = HashMap::new();
+        match self {
+            Self::Aes256 => {
+                headers.insert(SSE.to_string(), "AES256".to_string());
+            }
+            Self::Kms { key_id, context } => {
+                headers.insert(SSE.to_string(), "aws:kms".to_string());
+                if let Some(key_id) = key_id {
+                    headers.insert(format!("{}-aws-kms-key-id", SSE), key_id.clone());
+                }
+                if !context.is_empty() {
+                    headers.insert(
+                        format!("{}-context", SSE),
+                        base64::encode(json_object(context)),
+                    );
+                }
+            }
+            Self::Customer(key) => headers.extend(key.headers(SSE_CUSTOMER)),
+        }
+        headers
+    }
+
+    //UploadPart and UploadPartCopy repeat only the SSE-C key
+    pub(super) fn part_headers(&self) -> MHeader {
+        match self {
+            Self::Customer(key) => key.headers(SSE_CUSTOMER),
+            _ => HashMap::new(),
+        }
+    }
+}
+
+impl CustomerKey {
+    pub fn new(key: [u8; 32]) -> Self {
+        Self(key)
+    }
+
+    pub fn from_slice(key: &[u8]) -> S3Result<Self> {
+        let key = key
+            .try_into()
+            .map_err(|_| S3Error::other("SSE-C key must be 32 bytes"))?;
+        Ok(Self(key))
+    }
+
+    //algorithm, base64 key and base64 MD5 of the key, prefix is SSE_CUSTOMER
+    //or COPY_SOURCE_SSE_CUSTOMER
+    pub(super) fn headers(&self, prefix: &str) -> MHeader {
+        let mut headers = HashMap::new();
+        headers.insert(format!("{}-algorithm", prefix), "AES256".to_string());
+        headers.insert(format!("{}-key", prefix), base64::encode(self.0));
+        headers.insert(format!("{}-key-MD5", prefix), util::md5(self.0));
+        headers
+    }
+}
+
+//the key never shows up in logs
+impl fmt::Debug for CustomerKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("CustomerKey(..)")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sse_headers() {
+        let headers = ServerSideEncryption::Aes256.headers();
+        assert_eq!(headers.len(), 1);
+        assert_eq!(headers[SSE], "AES256");
+        assert!(ServerSideEncryption::Aes256.part_headers().is_empty());
+
+        let mut context = HashMap::new();
+        context.insert("project".to_string(), "a\"b".to_string());
+        context.insert("env".to_string(), "prod".to_string());
+        let kms = ServerSideEncryption::Kms {
+            key_id: Some("arn:aws:kms:us-east-1:123456789012:key/abcd".to_string()),
+            context,
+        };
+        let headers = kms.headers();
+        assert_eq!(headers[SSE], "aws:kms");
+        assert_eq!(
+            headers["x-amz-server-side-encryption-aws-kms-key-id"],
+            "arn:aws:kms:us-east-1:123456789012:key/abcd"
+        );
+        let context = base64::decode(&headers["x-amz-server-side-encryption-context"]).unwrap();
+        assert_eq!(
+            String::from_utf8(context).unwrap(),
+            r#"{"env":"prod","project":"a\"b"}"#
+        );
+        assert!(kms.part_headers().is_empty());
+    }
+
+    #[test]
+    fn sse_customer_key() {
+        let key = CustomerKey::new([0x61; 32]);
+        assert_eq!(format!("{:?}", key), "CustomerKey(..)");
+        assert!(CustomerKey::from_slice(&[0; 16]).is_err());
+        assert_eq!(CustomerKey::from_slice(&[0x61; 32]).unwrap(), key);
+
+        let sse = ServerSideEncryption::Customer(key.clone());
+        let headers = sse.headers();
+        assert_eq!(headers.len(), 3);
+        assert_eq!(
+            headers["x-amz-server-side-encryption-customer-algorithm"],
+            "AES256"
+        );
+        assert_eq!(
+            headers["x-amz-server-side-encryption-customer-key"],
+            "YWFhYWFhYWFhYWFhYWFhYWFhYWFhYWFhYWFhYWFhYWE="
+        );
+        assert_eq!(
+            headers["x-amz-server-side-encryption-customer-key-MD5"],
+            util::md5(vec![0x61; 32])
+        );
+        assert_eq!(sse.part_headers(), headers);
+
+        let copy = key.headers(COPY_SOURCE_SSE_CUSTOMER);
+        assert!(copy.contains_key("x-amz-copy-source-server-side-encryption-customer-key-MD5"));
+    }
+}
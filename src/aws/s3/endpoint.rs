@@ -0,0 +1,396 @@
+// https://docs.aws.amazon.com/AmazonS3/latest/userguide/VirtualHosting.html
+// https://docs.aws.amazon.com/general/latest/gr/s3.html
+use crate::util::uri_encode;
+use simple_error::{SimpleError, SimpleResult};
+use url::Url;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Scheme {
+    Https,
+    Http,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Addressing {
+    //virtual-hosted for AWS unless the bucket name is not a valid host
+    //label or contains dots, path-style for custom hosts
+    Auto,
+    //bucket.host/key
+    VirtualHosted,
+    //host/bucket/key
+    PathStyle,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Partition {
+    Aws,
+    AwsCn,
+    AwsUsGov,
+}
+
+impl Partition {
+    pub fn from_region(region: &str) -> Self {
+        if region.starts_with("cn-") {
+            Self::AwsCn
+        } else if region.starts_with("us-gov-") {
+            Self::AwsUsGov
+        } else {
+            Self::Aws
+        }
+    }
+
+    pub fn dns_suffix(&self) -> &'static str {
+        match self {
+            Self::AwsCn => "amazonaws.com.cn",
+            Self::Aws | Self::AwsUsGov => "amazonaws.com",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Endpoint {
+    pub scheme: Scheme,
+    //host[:port] of an S3 compatible service, None for the AWS regional host
+    pub host: Option<String>,
+    pub addressing: Addressing,
+    //the options below apply to AWS hosts only
+    pub dualstack: bool,
+    pub fips: bool,
+    //Transfer Acceleration, needs virtual-hosted addressing
+    pub accelerate: bool,
+}
+
+impl Default for Endpoint {
+    fn default() -> Self {
+        Self {
+            scheme: Scheme::Https,
+            host: None,
+            addressing: Addressing::Auto,
+            dualstack: false,
+            fips: false,
+            accelerate: false,
+        }
+    }
+}
+
+impl Endpoint {
+    //scheme, host and port of url, e.g. "http://localhost:9000" for MinIO
+    pub fn custom(url: &str) -> SimpleResult<Self> {
+        let url =
+            Url::parse(url).map_err(|e| SimpleError::new(format!("invalid endpoint: {}", e)))?;
+        let scheme = match url.scheme() {
+            "https" => Scheme::Https,
+            "http" => Scheme::Http,
+            other => return Err(SimpleError::new(format!("unsupported scheme {}", other))),
+        };
+        let host = url
+            .host_str()
+            .ok_or_else(|| SimpleError::new("endpoint has no host"))?;
+        let host = match url.port() {
+            Some(port) => format!("{}:{}", host, port),
+            None => host.to_string(),
+        };
+        Ok(Self {
+            scheme,
+            host: Some(host),
+            ..Default::default()
+        })
+    }
+
+    pub fn with_scheme(mut self, scheme: Scheme) -> Self {
+        self.scheme = scheme;
+        self
+    }
+
+    pub fn with_addressing(mut self, addressing: Addressing) -> Self {
+        self.addressing = addressing;
+        self
+    }
+
+    //IPv4 and IPv6 host
+    pub fn with_dualstack(mut self, dualstack: bool) -> Self {
+        self.dualstack = dualstack;
+        self
+    }
+
+    //FIPS 140-2 validated host, available in the US and GovCloud regions
+    pub fn with_fips(mut self, fips: bool) -> Self {
+        self.fips = fips;
+        self
+    }
+
+    pub fn with_accelerate(mut self, accelerate: bool) -> Self {
+        self.accelerate = accelerate;
+        self
+    }
+
+    //service host without the bucket
+    pub fn host(&self, region: &str) -> SimpleResult<String> {
+        if let Some(host) = &self.host {
+            if self.dualstack || self.fips || self.accelerate {
+                return Err(SimpleError::new(
+                    "dualstack, fips and accelerate apply to AWS endpoints only",
+                ));
+            }
+            return Ok(host.clone());
+        }
+
+        let partition = Partition::from_region(region);
+        if self.accelerate {
+            if self.fips || partition != Partition::Aws {
+                return Err(SimpleError::new(format!(
+                    "transfer acceleration is not available with fips or in {}",
+                    region
+                )));
+            }
+            return Ok(match self.dualstack {
+                true => "s3-accelerate.dualstack.amazonaws.com".to_string(),
+                false => "s3-accelerate.amazonaws.com".to_string(),
+            });
+        }
+        if self.fips && partition == Partition::AwsCn {
+            return Err(SimpleError::new(format!(
+                "fips is not available in {}",
+                region
+            )));
+        }
+
+        let mut host = match self.fips {
+            true => "s3-fips".to_string(),
+            false => "s3".to_string(),
+        };
+        if self.dualstack {
+            host.push_str(".dualstack");
+        }
+        //legacy global host
+        if host == "s3" && region == "us-east-1" {
+            return Ok("s3.amazonaws.com".to_string());
+        }
+        Ok(format!("{}.{}.{}", host, region, partition.dns_suffix()))
+    }
+
+    pub fn path_style(&self, bucket: &str) -> bool {
+        match self.addressing {
+            Addressing::PathStyle => true,
+            Addressing::VirtualHosted => false,
+            Addressing::Auto => self.host.is_some() || !virtual_host_bucket(bucket),
+        }
+    }
+
+    //Host header and object url without query string, an empty bucket
+    //addresses the service and an empty key the bucket
+    pub fn url(&self, region: &str, bucket: &str, key: &str) -> SimpleResult<(String, String)> {
+        let scheme = match self.scheme {
+            Scheme::Https => "https",
+            Scheme::Http => "http",
+        };
+        let host = self.host(region)?;
+        let key = uri_encode(key, false);
+        if self.accelerate && (bucket.is_empty() || self.path_style(bucket)) {
+            return Err(SimpleError::new(format!(
+                "transfer acceleration needs a virtual-hosted bucket, got \"{}\"",
+                bucket
+            )));
+        }
+
+        if bucket.is_empty() {
+            let url = format!("{}://{}/{}", scheme, host, key);
+            Ok((host, url))
+        } else if self.path_style(bucket) {
+            let url = format!("{}://{}/{}/{}", scheme, host, bucket, key);
+            Ok((host, url))
+        } else {
+            let host = format!("{}.{}", bucket, host);
+            let url = format!("{}://{}/{}", scheme, host, key);
+            Ok((host, url))
+        }
+    }
+}
+
+//dots break the TLS wildcard certificate of virtual hosts
+fn virtual_host_bucket(bucket: &str) -> bool {
+    (3..=63).contains(&bucket.len())
+        && !bucket.contains('.')
+        && !bucket.starts_with('-')
+        && !bucket.ends_with('-')
+        && bucket
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn endpoint_aws() {
+        let endpoint = Endpoint::default();
+        assert_eq!(
+            endpoint
+                .url("us-east-1", "examplebucket", "a b/c.txt")
+                .unwrap(),
+            (
+                "examplebucket.s3.amazonaws.com".to_string(),
+                "https://examplebucket.s3.amazonaws.com/a%20b/c.txt".to_string()
+            )
+        );
+        assert_eq!(
+            endpoint.url("eu-west-1", "examplebucket", "").unwrap(),
+            (
+                "examplebucket.s3.eu-west-1.amazonaws.com".to_string(),
+                "https://examplebucket.s3.eu-west-1.amazonaws.com/".to_string()
+            )
+        );
+        //dotted and non DNS compatible names fall back to path-style
+        assert_eq!(
+            endpoint.url("eu-west-1", "my.bucket", "k").unwrap(),
+            (
+                "s3.eu-west-1.amazonaws.com".to_string(),
+                "https://s3.eu-west-1.amazonaws.com/my.bucket/k".to_string()
+            )
+        );
+        assert!(endpoint.path_style("My_Bucket"));
+        assert_eq!(
+            endpoint.url("us-west-2", "", "").unwrap().1,
+            "https://s3.us-west-2.amazonaws.com/"
+        );
+
+        let endpoint = Endpoint::default()
+            .with_scheme(Scheme::Http)
+            .with_addressing(Addressing::PathStyle);
+        assert_eq!(
+            endpoint.url("us-west-2", "examplebucket", "k").unwrap().1,
+            "http://s3.us-west-2.amazonaws.com/examplebucket/k"
+        );
+        let endpoint = Endpoint::default().with_addressing(Addressing::VirtualHosted);
+        assert_eq!(
+            endpoint.url("us-west-2", "my.bucket", "k").unwrap().0,
+            "my.bucket.s3.us-west-2.amazonaws.com"
+        );
+    }
+
+    #[test]
+    fn endpoint_custom() {
+        let endpoint = Endpoint::custom("http://localhost:9000").unwrap();
+        assert_eq!(
+            endpoint.url("us-east-1", "bucket", "k").unwrap(),
+            (
+                "localhost:9000".to_string(),
+                "http://localhost:9000/bucket/k".to_string()
+            )
+        );
+
+        //default ports are left out of the Host header
+        let endpoint = Endpoint::custom("https://account.r2.cloudflarestorage.com:443")
+            .unwrap()
+            .with_addressing(Addressing::VirtualHosted);
+        assert_eq!(
+            endpoint.url("auto", "bucket", "k").unwrap(),
+            (
+                "bucket.account.r2.cloudflarestorage.com".to_string(),
+                "https://bucket.account.r2.cloudflarestorage.com/k".to_string()
+            )
+        );
+
+        assert!(Endpoint::custom("ftp://host").is_err());
+        assert!(Endpoint::custom("not a url").is_err());
+        let endpoint = Endpoint::custom("http://localhost:9000")
+            .unwrap()
+            .with_dualstack(true);
+        assert!(endpoint.url("us-east-1", "bucket", "k").is_err());
+    }
+
+    fn host(endpoint: &Endpoint, region: &str) -> Option<String> {
+        endpoint.host(region).ok()
+    }
+
+    #[test]
+    fn endpoint_partitions() {
+        let plain = Endpoint::default();
+        let dualstack = Endpoint::default().with_dualstack(true);
+        let fips = Endpoint::default().with_fips(true);
+        let fips_dualstack = fips.clone().with_dualstack(true);
+        let accelerate = Endpoint::default().with_accelerate(true);
+        let accelerate_dualstack = accelerate.clone().with_dualstack(true);
+        let accelerate_fips = accelerate.clone().with_fips(true);
+
+        let cases = [
+            (&plain, "us-east-1", Some("s3.amazonaws.com")),
+            (&plain, "us-west-2", Some("s3.us-west-2.amazonaws.com")),
+            (&plain, "cn-north-1", Some("s3.cn-north-1.amazonaws.com.cn")),
+            (
+                &plain,
+                "us-gov-west-1",
+                Some("s3.us-gov-west-1.amazonaws.com"),
+            ),
+            (
+                &dualstack,
+                "us-east-1",
+                Some("s3.dualstack.us-east-1.amazonaws.com"),
+            ),
+            (
+                &dualstack,
+                "cn-northwest-1",
+                Some("s3.dualstack.cn-northwest-1.amazonaws.com.cn"),
+            ),
+            (
+                &dualstack,
+                "us-gov-east-1",
+                Some("s3.dualstack.us-gov-east-1.amazonaws.com"),
+            ),
+            (&fips, "us-east-1", Some("s3-fips.us-east-1.amazonaws.com")),
+            (
+                &fips,
+                "us-gov-west-1",
+                Some("s3-fips.us-gov-west-1.amazonaws.com"),
+            ),
+            (&fips, "cn-north-1", None),
+            (
+                &fips_dualstack,
+                "us-east-2",
+                Some("s3-fips.dualstack.us-east-2.amazonaws.com"),
+            ),
+            (
+                &fips_dualstack,
+                "us-gov-west-1",
+                Some("s3-fips.dualstack.us-gov-west-1.amazonaws.com"),
+            ),
+            (&fips_dualstack, "cn-north-1", None),
+            (
+                &accelerate,
+                "eu-west-1",
+                Some("s3-accelerate.amazonaws.com"),
+            ),
+            (&accelerate, "cn-north-1", None),
+            (&accelerate, "us-gov-west-1", None),
+            (
+                &accelerate_dualstack,
+                "us-east-1",
+                Some("s3-accelerate.dualstack.amazonaws.com"),
+            ),
+            (&accelerate_dualstack, "cn-north-1", None),
+            (&accelerate_fips, "us-east-1", None),
+        ];
+        for (endpoint, region, expected) in cases.iter() {
+            assert_eq!(
+                host(endpoint, region),
+                expected.map(|h| h.to_string()),
+                "{:?} {}",
+                endpoint,
+                region
+            );
+        }
+
+        assert_eq!(
+            accelerate.url("eu-west-1", "bucket", "k").unwrap(),
+            (
+                "bucket.s3-accelerate.amazonaws.com".to_string(),
+                "https://bucket.s3-accelerate.amazonaws.com/k".to_string()
+            )
+        );
+        assert!(accelerate.url("eu-west-1", "my.bucket", "k").is_err());
+        assert!(accelerate.url("eu-west-1", "", "").is_err());
+        let path_style = accelerate.with_addressing(Addressing::PathStyle);
+        assert!(path_style.url("eu-west-1", "bucket", "k").is_err());
+    }
+}
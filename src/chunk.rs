@@ -1,13 +1,20 @@
+use crate::progress::{Monitor, ProgressFn, RateLimiter};
+use std::fmt;
 use std::io::{Read, Result};
 use std::iter::Iterator;
 
-#[derive(Debug)]
+//progress and rate limit are applied by the Monitor around the items
 pub struct Chunk<I>
 where
     I: Iterator<Item = Vec<u8>>,
 {
+    inner: Monitor<Items<I>>,
+}
+
+struct Items<I> {
     finished: bool,
     buffer: Vec<u8>,
+    pos: usize,
     producer: I,
     total_bytes: usize,
 }
@@ -17,16 +24,55 @@ where
     I: Iterator<Item = Vec<u8>>,
 {
     pub fn new(producer: I) -> Self {
-        Self {
+        let items = Items {
             finished: false,
-            buffer: Vec::with_capacity(1024 * 1024),
+            buffer: Vec::new(),
+            pos: 0,
             producer,
             total_bytes: 0,
+        };
+        Self {
+            inner: Monitor::new(items, None),
         }
     }
+
+    //called after every read with the bytes handed out so far
+    pub fn with_progress(mut self, total: Option<u64>, progress: ProgressFn) -> Self {
+        self.inner = self.inner.with_total(total).with_progress(Some(progress));
+        self
+    }
+
+    pub fn with_rate_limit(mut self, limiter: RateLimiter) -> Self {
+        self.inner = self.inner.with_rate_limit(Some(limiter));
+        self
+    }
+}
+
+impl<I> fmt::Debug for Chunk<I>
+where
+    I: Iterator<Item = Vec<u8>>,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let items = self.inner.get_ref();
+        f.debug_struct("Chunk")
+            .field("finished", &items.finished)
+            .field("total_bytes", &items.total_bytes)
+            .field("sent", &self.inner.sent())
+            .field("total", &self.inner.total())
+            .finish()
+    }
 }
 
 impl<I> Read for Chunk<I>
+where
+    I: Iterator<Item = Vec<u8>>,
+{
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        self.inner.read(buf)
+    }
+}
+
+impl<I> Read for Items<I>
 where
     I: Iterator<Item = Vec<u8>>,
 {
@@ -35,40 +81,35 @@ where
             return Ok(0);
         }
 
-        let len1 = buf.len();
-        while self.buffer.len() < len1 {
+        //hand out the current item before pulling the next one
+        while self.pos >= self.buffer.len() {
             match self.producer.next() {
                 Some(bytes) => {
                     //for debug only
                     self.total_bytes += bytes.len();
 
-                    self.buffer.extend(bytes);
+                    self.buffer = bytes;
+                    self.pos = 0;
+                }
+                None => {
+                    self.finished = true;
+                    log::debug!("Chunk data finished, total {}", self.total_bytes);
+                    return Ok(0);
                 }
-                None => break,
             }
         }
 
-        let len2 = std::cmp::min(len1, self.buffer.len());
-        let vec1: Vec<u8> = self.buffer.drain(0..len2).collect();
-        for (n, &x) in vec1.iter().enumerate() {
-            buf[n] = x;
-        }
-
-        self.finished = self.buffer.is_empty();
-        if self.finished {
-            log::debug!(
-                "Chunk data finished, buf {}, total {}",
-                buf.len(),
-                self.total_bytes
-            );
-        }
-        Ok(len2)
+        let len = std::cmp::min(buf.len(), self.buffer.len() - self.pos);
+        buf[..len].copy_from_slice(&self.buffer[self.pos..self.pos + len]);
+        self.pos += len;
+        Ok(len)
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::progress::Progress;
 
     #[test]
     fn chunk_read() {
@@ -92,4 +133,34 @@ mod tests {
         let result = chunk1.read(&mut buf2);
         assert_eq!(result.unwrap(), 0);
     }
+
+    #[test]
+    fn chunk_read_all() {
+        let producer = vec![b"abc".to_vec(), vec![], b"defgh".to_vec(), b"i".to_vec()];
+        let mut chunk = Chunk::new(producer.into_iter());
+        let mut out = Vec::new();
+        chunk.read_to_end(&mut out).expect("read failed");
+        assert_eq!(out, b"abcdefghi".to_vec());
+    }
+
+    #[test]
+    fn chunk_progress() {
+        use std::sync::{Arc, Mutex};
+
+        let last = Arc::new(Mutex::new(None));
+        let last1 = last.clone();
+        let producer = vec![vec![0u8; 300], vec![0u8; 200]];
+        let mut chunk = Chunk::new(producer.into_iter()).with_progress(
+            Some(500),
+            Arc::new(move |p| *last1.lock().unwrap() = Some(p)),
+        );
+        std::io::copy(&mut chunk, &mut std::io::sink()).expect("copy failed");
+        assert_eq!(
+            *last.lock().unwrap(),
+            Some(Progress {
+                sent: 500,
+                total: Some(500)
+            })
+        );
+    }
 }
@@ -3,4 +3,5 @@ pub mod aliyun;
 #[cfg(feature = "aws")]
 pub mod aws;
 pub mod chunk;
+pub mod progress;
 pub mod util;
@@ -0,0 +1,202 @@
+use std::io::{Read, Result, Seek, SeekFrom};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Progress {
+    //bytes handed to the transport so far
+    pub sent: u64,
+    //expected total, None when the length is unknown
+    pub total: Option<u64>,
+}
+
+pub type ProgressFn = Arc<dyn Fn(Progress) + Send + Sync>;
+
+//token bucket shared by every clone, so parallel uploads share one budget
+#[derive(Clone)]
+pub struct RateLimiter {
+    bucket: Arc<Mutex<Bucket>>,
+}
+
+struct Bucket {
+    rate: f64,
+    burst: f64,
+    tokens: f64,
+    last: Instant,
+}
+
+impl RateLimiter {
+    //bytes_per_sec with a burst of one second worth of tokens
+    pub fn new(bytes_per_sec: u64) -> Self {
+        Self::with_burst(bytes_per_sec, bytes_per_sec)
+    }
+
+    pub fn with_burst(bytes_per_sec: u64, burst: u64) -> Self {
+        let rate = bytes_per_sec.max(1) as f64;
+        let burst = burst.max(1) as f64;
+        Self {
+            bucket: Arc::new(Mutex::new(Bucket {
+                rate,
+                burst,
+                tokens: burst,
+                last: Instant::now(),
+            })),
+        }
+    }
+
+    //largest amount that can be taken at once
+    pub fn burst(&self) -> usize {
+        self.bucket.lock().expect("rate limiter poisoned").burst as usize
+    }
+
+    //block until n bytes may be sent
+    pub fn acquire(&self, n: usize) {
+        let mut left = n as f64;
+        while left > 0.0 {
+            let wait = {
+                let mut bucket = self.bucket.lock().expect("rate limiter poisoned");
+                let now = Instant::now();
+                let elapsed = now.duration_since(bucket.last).as_secs_f64();
+                bucket.tokens = (bucket.tokens + elapsed * bucket.rate).min(bucket.burst);
+                bucket.last = now;
+
+                let take = left.min(bucket.burst);
+                if bucket.tokens >= take {
+                    bucket.tokens -= take;
+                    left -= take;
+                    None
+                } else {
+                    Some((take - bucket.tokens) / bucket.rate)
+                }
+            };
+            if let Some(secs) = wait {
+                std::thread::sleep(Duration::from_secs_f64(secs));
+            }
+        }
+    }
+}
+
+//Read wrapper reporting progress and applying the rate limit
+pub struct Monitor<R: Read> {
+    inner: R,
+    sent: u64,
+    total: Option<u64>,
+    progress: Option<ProgressFn>,
+    limiter: Option<RateLimiter>,
+}
+
+impl<R: Read> Monitor<R> {
+    pub fn new(inner: R, total: Option<u64>) -> Self {
+        Self {
+            inner,
+            sent: 0,
+            total,
+            progress: None,
+            limiter: None,
+        }
+    }
+
+    pub fn with_progress(mut self, progress: Option<ProgressFn>) -> Self {
+        self.progress = progress;
+        self
+    }
+
+    pub fn with_rate_limit(mut self, limiter: Option<RateLimiter>) -> Self {
+        self.limiter = limiter;
+        self
+    }
+
+    //bytes already sent before the first read, e.g. by an earlier attempt
+    pub fn with_sent(mut self, sent: u64) -> Self {
+        self.sent = sent;
+        self
+    }
+
+    pub fn with_total(mut self, total: Option<u64>) -> Self {
+        self.total = total;
+        self
+    }
+
+    pub fn sent(&self) -> u64 {
+        self.sent
+    }
+
+    pub fn total(&self) -> Option<u64> {
+        self.total
+    }
+
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+}
+
+impl<R: Read> Read for Monitor<R> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        let max = match &self.limiter {
+            Some(limiter) => std::cmp::min(buf.len(), limiter.burst()),
+            None => buf.len(),
+        };
+        let len = self.inner.read(&mut buf[..max])?;
+        if len > 0 {
+            if let Some(limiter) = &self.limiter {
+                limiter.acquire(len);
+            }
+            self.sent += len as u64;
+            if let Some(progress) = &self.progress {
+                progress(Progress {
+                    sent: self.sent,
+                    total: self.total,
+                });
+            }
+        }
+        Ok(len)
+    }
+}
+
+//seeking moves the source only, sent keeps counting the bytes read
+impl<R: Read + Seek> Seek for Monitor<R> {
+    fn seek(&mut self, pos: SeekFrom) -> Result<u64> {
+        self.inner.seek(pos)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn monitor_progress() {
+        let reports = Arc::new(Mutex::new(Vec::new()));
+        let reports1 = reports.clone();
+        let progress: ProgressFn = Arc::new(move |p| reports1.lock().unwrap().push(p));
+
+        let data = [1u8; 2500];
+        let mut monitor = Monitor::new(&data[..], Some(2500)).with_progress(Some(progress));
+        let mut buf = [0u8; 1000];
+        while monitor.read(&mut buf).unwrap() > 0 {}
+
+        let reports = reports.lock().unwrap();
+        let sent = reports.iter().map(|p| p.sent).collect::<Vec<u64>>();
+        assert_eq!(sent, vec![1000, 2000, 2500]);
+        assert!(reports.iter().all(|p| p.total == Some(2500)));
+    }
+
+    #[test]
+    fn monitor_rate_limit() {
+        //20kB at 100kB/s with an empty bucket takes at least 0.15s
+        let limiter = RateLimiter::with_burst(100_000, 5_000);
+        limiter.acquire(5_000);
+
+        let data = vec![0u8; 20_000];
+        let mut monitor = Monitor::new(&data[..], None).with_rate_limit(Some(limiter));
+        let start = Instant::now();
+        let mut out = Vec::new();
+        monitor.read_to_end(&mut out).unwrap();
+        assert_eq!(out.len(), 20_000);
+        assert!(start.elapsed() >= Duration::from_millis(150));
+    }
+}
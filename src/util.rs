@@ -14,9 +14,9 @@ pub fn get_date_long() -> String {
     Utc::now().format(LONG_DATETIME).to_string()
 }
 
-#[cfg(feature = "aliyun")]
-pub fn md5(content: Vec<u8>) -> String {
-    base64::encode(*md5::compute(&content))
+#[cfg(any(feature = "aliyun", feature = "aws_client"))]
+pub fn md5<T: AsRef<[u8]>>(content: T) -> String {
+    base64::encode(*md5::compute(content))
 }
 
 pub trait Headers: IntoIterator<Item = (String, String)> + Clone
@@ -61,12 +61,12 @@ pub fn uri_encode(string: &str, encode_slash: bool) -> String {
             'a'..='z' | 'A'..='Z' | '0'..='9' | '_' | '-' | '~' | '.' => result.push(c),
             '/' if encode_slash => result.push_str("%2F"),
             '/' if !encode_slash => result.push('/'),
+            //every byte of a multi-byte character is escaped
             _ => {
-                result.push('%');
                 result.push_str(
                     &format!("{}", c)
                         .bytes()
-                        .map(|b| format!("{:02X}", b))
+                        .map(|b| format!("%{:02X}", b))
                         .collect::<String>(),
                 );
             }
@@ -138,6 +138,13 @@ mod tests {
         println!("{:?}", date2_utc);
     }
 
+    #[test]
+    fn util_uri_encode() {
+        assert_eq!(uri_encode("a b/c~d", false), "a%20b/c~d");
+        assert_eq!(uri_encode("a b/c", true), "a%20b%2Fc");
+        assert_eq!(uri_encode("ü€", true), "%C3%BC%E2%82%AC");
+    }
+
     #[test]
     fn util_concat() {
         let data = [97u8; 1024];
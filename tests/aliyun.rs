@@ -18,7 +18,7 @@ fn ali_putobject() {
 
     let format_date = util::get_date_gmt();
 
-    let content_md5 = util::md5(body1.as_bytes().to_vec());
+    let content_md5 = util::md5(body1);
     let content_type = "text/plain".to_string();
 
     let h1 = "X-OSS-Meta-Author".to_string();
@@ -40,14 +40,15 @@ fn ali_putobject() {
         key_secret,
     };
 
-    let mut list = Vec::new();
-    list.push(("Authorization".to_string(), auth.make_authorization()));
-    list.push(("Host".to_string(), host));
-    list.push(("Content-Type".to_string(), content_type));
-    list.push(("Content-Md5".to_string(), content_md5));
-    list.push(("Date".to_string(), format_date.clone()));
-    list.push((h1, v1));
-    list.push((h2, v2));
+    let list = vec![
+        ("Authorization".to_string(), auth.make_authorization()),
+        ("Host".to_string(), host),
+        ("Content-Type".to_string(), content_type),
+        ("Content-Md5".to_string(), content_md5),
+        ("Date".to_string(), format_date.clone()),
+        (h1, v1),
+        (h2, v2),
+    ];
 
     let mut request = ureq::put(&url1);
 
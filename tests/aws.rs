@@ -6,7 +6,7 @@ mod tests {
     use log::debug;
     use sloppy_auth::{
         aws::{auth, s3},
-        chunk, util,
+        util,
     };
     use std::collections::HashMap;
     use std::env;
@@ -42,7 +42,7 @@ mod tests {
             url: Url::parse(&url2).expect("url parse failed"),
             datetime: date,
             region: "us-east-1".to_string(),
-            access_key: access_key,
+            access_key,
             secret_key: access_secret,
             headers: headers.clone(),
             hash_request_payload: s3::UNSIGNED_PAYLOAD.to_string(),
@@ -115,7 +115,7 @@ mod tests {
             url: Url::parse(&full_url).expect("url parse failed"),
             datetime: date,
             region: "us-east-1".to_string(),
-            access_key: access_key,
+            access_key,
             secret_key: access_secret,
             headers: headers.clone(),
             hash_request_payload: s3::STREAM_PAYLOAD.to_string(),
@@ -124,12 +124,11 @@ mod tests {
         headers.insert("Authorization".to_string(), signer.sign());
 
         let holder = Holder::new(6 * 1024 * 1024, rd1, signer);
-        let chunk = chunk::Chunk::new(holder);
         let mut request2 = ureq::put(&full_url);
         for (k, v) in headers {
             request2 = request2.set(&k, &v);
         }
-        match request2.send(chunk) {
+        match request2.send(holder) {
             Ok(resp) => {
                 log::debug!("ok {}", resp.status());
             }
@@ -157,6 +156,7 @@ mod tests {
             key: "test3".to_string(),
             content_len: bytes_len.to_string(),
             data: response1.into_reader(),
+            options: Default::default(),
         };
         client
             .put_object_stream(8 * 1024, input)
@@ -174,10 +174,455 @@ mod tests {
             key: "test4".to_string(),
             content_len: data.len().to_string(),
             data: data.as_bytes(),
+            options: Default::default(),
         };
         client.put_object(input).expect("put object single failed");
     }
 
+    #[test]
+    fn aws_s3_client_putobject_options() {
+        use s3::client::{ObjectOptions, PutObjectInput, StorageClass};
+        u2::init_log();
+        let client = s3::client::Client::new("us-east-1".to_string());
+
+        let data = "<p>hello</p>";
+        let mut options = ObjectOptions {
+            content_type: Some("text/html".to_string()),
+            cache_control: Some("max-age=60".to_string()),
+            storage_class: Some(StorageClass::StandardIa),
+            ..Default::default()
+        };
+        options
+            .metadata
+            .insert("owner".to_string(), "alice".to_string());
+        options
+            .tagging
+            .insert("env".to_string(), "test".to_string());
+        let input =
+            PutObjectInput::new("sls11", "test_options", data.len() as u64, data.as_bytes())
+                .with_options(options);
+        client
+            .upload(input)
+            .expect("put object with options failed");
+
+        let output = client
+            .head_object(s3::client::GetObjectInput::new("sls11", "test_options"))
+            .expect("head object failed");
+        let metadata = output.metadata().expect("test_options not found");
+        assert_eq!(metadata.content_type.as_deref(), Some("text/html"));
+        assert_eq!(metadata.metadata["owner"], "alice");
+    }
+
+    #[test]
+    fn aws_s3_client_sse_customer() {
+        use s3::client::{CopyObjectInput, ServerSideEncryption};
+        use s3::client::{CustomerKey, GetObjectInput, ObjectOptions, PutObjectInput};
+        use std::io::Read;
+        u2::init_log();
+        let client = s3::client::Client::new("us-east-1".to_string());
+
+        let key = CustomerKey::new([7; 32]);
+        let data = "secret";
+        let options = ObjectOptions {
+            encryption: Some(ServerSideEncryption::Customer(key.clone())),
+            ..Default::default()
+        };
+        let input = PutObjectInput::new("sls11", "test_ssec", data.len() as u64, data.as_bytes())
+            .with_options(options.clone());
+        client
+            .put_object(input)
+            .expect("put object with SSE-C failed");
+
+        let mut input = GetObjectInput::new("sls11", "test_ssec");
+        assert!(client.get_object(input.clone()).is_err());
+        input.sse_customer_key = Some(key.clone());
+        let mut body = String::new();
+        client
+            .get_object(input)
+            .expect("get object with SSE-C failed")
+            .body
+            .read_to_string(&mut body)
+            .expect("read body failed");
+        assert_eq!(body, data);
+
+        //the low-level multipart calls take the same options
+        let upload_id = client
+            .create_multipart_upload_with("sls11", "test_ssec_multipart", &options)
+            .expect("create multipart upload with SSE-C failed");
+        let part = client
+            .upload_part_with(
+                "sls11",
+                "test_ssec_multipart",
+                &upload_id,
+                1,
+                data.as_bytes(),
+                &options,
+            )
+            .expect("upload part with SSE-C failed");
+        client
+            .complete_multipart_upload("sls11", "test_ssec_multipart", &upload_id, &[part])
+            .expect("complete multipart upload failed");
+
+        let mut input = CopyObjectInput::new("sls11", "test_ssec", "sls11", "test_ssec_copy");
+        input.source_sse_customer_key = Some(key);
+        input.encryption = Some(ServerSideEncryption::Aes256);
+        client.copy(&input).expect("copy SSE-C object failed");
+    }
+
+    #[test]
+    fn aws_s3_client_client_side_encryption() {
+        use s3::client::{GetObjectInput, MasterKey, PutObjectInput};
+        use std::io::Read;
+        u2::init_log();
+        let client = s3::client::Client::new("us-east-1".to_string())
+            .with_encryption(MasterKey::new([3; 32]));
+
+        let data = "for your eyes only";
+        let input = PutObjectInput::new("sls11", "test_cse", data.len() as u64, data.as_bytes());
+        client
+            .put_object(input)
+            .expect("put encrypted object failed");
+
+        let mut output = client
+            .get_object(GetObjectInput::new("sls11", "test_cse"))
+            .expect("get encrypted object failed");
+        let mut body = String::new();
+        output
+            .body
+            .read_to_string(&mut body)
+            .expect("read body failed");
+        assert_eq!(body, data);
+        assert_eq!(output.metadata.content_length, Some(data.len() as u64));
+
+        //stored encrypted
+        let plain = s3::client::Client::new("us-east-1".to_string());
+        let output = plain
+            .head_object(GetObjectInput::new("sls11", "test_cse"))
+            .expect("head object failed");
+        let metadata = output.metadata().expect("test_cse not found");
+        assert_eq!(metadata.content_length, Some(data.len() as u64 + 16));
+    }
+
+    #[test]
+    fn aws_s3_client_presign() {
+        use s3::client::{GetObjectInput, PresignPutInput};
+        use std::time::Duration;
+        u2::init_log();
+        let client = s3::client::Client::new("us-east-1".to_string());
+
+        let mut input = PresignPutInput::new("sls11", "test_presign");
+        input.options.content_type = Some("text/plain".to_string());
+        let put = client
+            .presign_put(&input, Duration::from_secs(300))
+            .expect("presign put failed");
+        let mut request = ureq::put(&put.url);
+        for (k, v) in &put.headers {
+            request = request.set(k, v);
+        }
+        request
+            .send_string("presigned")
+            .expect("presigned put failed");
+
+        let mut input = GetObjectInput::new("sls11", "test_presign");
+        input.response_content_disposition = Some("attachment; filename=\"a b.txt\"".to_string());
+        let get = client
+            .presign_get(&input, Duration::from_secs(300))
+            .expect("presign get failed");
+        let resp = ureq::get(&get.url).call().expect("presigned get failed");
+        assert_eq!(
+            resp.header("Content-Disposition"),
+            Some("attachment; filename=\"a b.txt\"")
+        );
+        assert_eq!(resp.into_string().expect("read body failed"), "presigned");
+    }
+
+    #[test]
+    fn aws_s3_client_presign_post() {
+        use s3::client::PostPolicy;
+        use std::io::Read;
+        use std::time::Duration;
+        u2::init_log();
+        let client = s3::client::Client::new("us-east-1".to_string());
+
+        let policy = PostPolicy::new("sls11", Duration::from_secs(300))
+            .with_key_prefix("test_post/")
+            .with_content_length_range(1, 1024)
+            .with_content_type("text/plain")
+            .with_metadata("owner", "tests");
+        let post = client.presign_post(&policy).expect("presign post failed");
+
+        //multipart/form-data with the file as the last field
+        let boundary = "----s3postboundary";
+        let mut body = String::new();
+        for (k, v) in &post.fields {
+            body.push_str(&format!(
+                "--{}\r\nContent-Disposition: form-data; name=\"{}\"\r\n\r\n{}\r\n",
+                boundary, k, v
+            ));
+        }
+        body.push_str(&format!(
+            "--{}\r\nContent-Disposition: form-data; name=\"file\"; filename=\"a.txt\"\r\n\
+             Content-Type: text/plain\r\n\r\nposted\r\n--{}--\r\n",
+            boundary, boundary
+        ));
+        ureq::post(&post.url)
+            .set(
+                "Content-Type",
+                &format!("multipart/form-data; boundary={}", boundary),
+            )
+            .send_string(&body)
+            .expect("post upload failed");
+
+        let input = s3::client::GetObjectInput::new("sls11", "test_post/a.txt");
+        let mut output = client.get_object(input).expect("get object failed");
+        let mut text = String::new();
+        output
+            .body
+            .read_to_string(&mut text)
+            .expect("read body failed");
+        assert_eq!(text, "posted");
+        assert_eq!(output.metadata.metadata["owner"], "tests");
+    }
+
+    #[test]
+    fn aws_s3_client_getobject_range() {
+        use std::io::Read;
+        u2::init_log();
+        let client = s3::client::Client::new("us-east-1".to_string());
+
+        let data = "abcd";
+        let input = s3::client::PutObjectInput {
+            bucket: "sls11".to_string(),
+            key: "test5".to_string(),
+            content_len: data.len().to_string(),
+            data: data.as_bytes(),
+            options: Default::default(),
+        };
+        client.put_object(input).expect("put object single failed");
+
+        let input = s3::client::GetObjectInput::new("sls11", "test5").with_range(1, Some(2));
+        let mut output = client.get_object(input).expect("get object failed");
+        let mut body = String::new();
+        output
+            .body
+            .read_to_string(&mut body)
+            .expect("read body failed");
+        assert_eq!(body, "bc");
+        assert_eq!(output.metadata.content_length, Some(2));
+        assert!(output.metadata.etag.is_some());
+    }
+
+    #[test]
+    fn aws_s3_client_headobject() {
+        u2::init_log();
+        let client = s3::client::Client::new("us-east-1".to_string());
+
+        let input = s3::client::GetObjectInput::new("sls11", "test4");
+        let output = client.head_object(input).expect("head object failed");
+        let metadata = output.metadata().expect("test4 not found");
+        assert_eq!(metadata.content_length, Some(4));
+
+        let exists = client
+            .object_exists("sls11", "no-such-key")
+            .expect("head object failed");
+        assert!(!exists);
+    }
+
+    #[test]
+    fn aws_s3_client_deleteobjects() {
+        use s3::client::{ObjectIdentifier, PutObjectInput};
+        u2::init_log();
+        let client = s3::client::Client::new("us-east-1".to_string());
+
+        let keys = ["test-del1", "test-del2", "test del&3"];
+        for key in keys.iter() {
+            let input = PutObjectInput {
+                bucket: "sls11".to_string(),
+                key: key.to_string(),
+                content_len: "1".to_string(),
+                data: "a".as_bytes(),
+                options: Default::default(),
+            };
+            client.put_object(input).expect("put object single failed");
+        }
+
+        client
+            .delete_object("sls11", keys[0], None)
+            .expect("delete object failed");
+        let objects = keys[1..]
+            .iter()
+            .map(|key| ObjectIdentifier::new(key))
+            .collect::<Vec<ObjectIdentifier>>();
+        let output = client
+            .delete_objects("sls11", &objects, false)
+            .expect("delete objects failed");
+        assert_eq!(output.deleted.len(), 2);
+        assert!(output.errors.is_empty());
+    }
+
+    #[test]
+    fn aws_s3_client_listobjects() {
+        use s3::client::ListObjectsInput;
+        u2::init_log();
+        let client = s3::client::Client::new("us-east-1".to_string());
+
+        let mut input = ListObjectsInput::new("sls11");
+        input.prefix = Some("test".to_string());
+        input.max_keys = Some(1);
+        let mut keys = Vec::new();
+        for page in client.list_objects_v2_pages(input) {
+            let page = page.expect("list objects failed");
+            assert!(page.objects.len() <= 1);
+            keys.extend(page.objects.into_iter().map(|o| o.key));
+        }
+        assert!(keys.contains(&"test4".to_string()));
+    }
+
+    #[test]
+    fn aws_s3_client_upload_multipart() {
+        use std::io::Read;
+        u2::init_log();
+        let client = s3::client::Client::new("us-east-1".to_string()).with_multipart(0, 0);
+
+        //two full parts and a short one
+        let part = s3::client::MIN_PART_SIZE;
+        let data = (0..2 * part + 100)
+            .map(|n| (n % 251) as u8)
+            .collect::<Vec<u8>>();
+        let input = s3::client::PutObjectInput {
+            bucket: "sls11".to_string(),
+            key: "test-multipart".to_string(),
+            content_len: data.len().to_string(),
+            data: &data[..],
+            options: Default::default(),
+        };
+        client.upload(input).expect("multipart upload failed");
+
+        let input = s3::client::GetObjectInput::new("sls11", "test-multipart");
+        let mut output = client.get_object(input).expect("get object failed");
+        let mut body = Vec::new();
+        output
+            .body
+            .read_to_end(&mut body)
+            .expect("read body failed");
+        assert!(body == data);
+    }
+
+    #[test]
+    fn aws_s3_client_upload_parallel() {
+        u2::init_log();
+        let client = s3::client::Client::new("us-east-1".to_string()).with_multipart(0, 0);
+
+        let part = s3::client::MIN_PART_SIZE;
+        let data = vec![7u8; 3 * part + 100];
+        let input = s3::client::PutObjectInput {
+            bucket: "sls11".to_string(),
+            key: "test-parallel".to_string(),
+            content_len: data.len().to_string(),
+            data: &data[..],
+            options: Default::default(),
+        };
+        client
+            .parallel_uploader()
+            .with_concurrency(2)
+            .with_max_memory(3 * part)
+            .upload(input)
+            .expect("parallel upload failed");
+
+        let output = client
+            .head_object(s3::client::GetObjectInput::new("sls11", "test-parallel"))
+            .expect("head object failed");
+        let metadata = output.metadata().expect("test-parallel not found");
+        assert_eq!(metadata.content_length, Some(data.len() as u64));
+    }
+
+    #[test]
+    fn aws_s3_client_upload_resumable() {
+        u2::init_log();
+        let client = s3::client::Client::new("us-east-1".to_string()).with_multipart(0, 0);
+
+        let part = s3::client::MIN_PART_SIZE;
+        let data = vec![3u8; 2 * part + 100];
+        let state_path = env::temp_dir().join("sloppy_auth_test_resumable");
+        let mut input = s3::client::PutObjectInput::new(
+            "sls11",
+            "test-resumable",
+            data.len() as u64,
+            std::io::Cursor::new(&data),
+        );
+        input.options.content_type = Some("application/octet-stream".to_string());
+        input
+            .options
+            .metadata
+            .insert("source".to_string(), "resumable".to_string());
+        client
+            .upload_resumable(input, &state_path)
+            .expect("resumable upload failed");
+        assert!(!state_path.exists());
+
+        let output = client
+            .head_object(s3::client::GetObjectInput::new("sls11", "test-resumable"))
+            .expect("head object failed");
+        let metadata = output.metadata().expect("test-resumable not found");
+        assert_eq!(
+            metadata.content_type.as_deref(),
+            Some("application/octet-stream")
+        );
+        assert_eq!(metadata.metadata["source"], "resumable");
+    }
+
+    #[test]
+    fn aws_s3_client_copyobject() {
+        use s3::client::{CopyObjectInput, MetadataDirective};
+        u2::init_log();
+        let client = s3::client::Client::new("us-east-1".to_string());
+
+        let mut input = CopyObjectInput::new("sls11", "test4", "sls11", "copy/test4 copy");
+        input.metadata_directive = Some(MetadataDirective::Replace);
+        input.content_type = Some("text/plain".to_string());
+        let output = client.copy(&input).expect("copy object failed");
+        assert!(output.etag.is_some());
+
+        let output = client
+            .head_object(s3::client::GetObjectInput::new("sls11", "copy/test4 copy"))
+            .expect("head object failed");
+        let metadata = output.metadata().expect("copy not found");
+        assert_eq!(metadata.content_type.as_deref(), Some("text/plain"));
+    }
+
+    #[test]
+    fn aws_s3_client_putobject_seekable() {
+        use s3::client::{PutObjectInput, RetryPolicy, Transfer};
+        u2::init_log();
+        let client =
+            s3::client::Client::new("us-east-1".to_string()).with_retry(RetryPolicy::new(5));
+
+        let data = vec![7u8; 100 * 1024];
+        let input = PutObjectInput {
+            bucket: "sls11".to_string(),
+            key: "test_seekable".to_string(),
+            content_len: data.len().to_string(),
+            data: std::io::Cursor::new(&data),
+            options: Default::default(),
+        };
+        client
+            .put_object_seekable(64, input, Transfer::Multiple(data.len().to_string()))
+            .expect("seekable put failed");
+    }
+
+    #[test]
+    fn aws_s3_client_error() {
+        u2::init_log();
+        let client = s3::client::Client::new("us-east-1".to_string());
+
+        let input = s3::client::GetObjectInput::new("sls11", "no-such-key");
+        let error = client.get_object(input).err().expect("missing key found");
+        assert!(error.is_not_found());
+        let service = error.service().expect("not a service error");
+        assert_eq!(service.code, "NoSuchKey");
+        assert!(service.request_id.is_some());
+    }
+
     use s3::client::ChunkExt;
     #[test]
     fn aws_s3_client_save_remote() {